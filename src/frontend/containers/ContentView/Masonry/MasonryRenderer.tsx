@@ -5,7 +5,7 @@ import { useStore } from 'src/frontend/contexts/StoreContext';
 import FocusManager from 'src/frontend/FocusManager';
 import { ViewMethod } from 'src/frontend/stores/UiStore';
 import { debounce, throttle } from 'common/timeout';
-import { MasonryType } from 'wasm/packages/masonry';
+import { MasonryType, NavigationDirection } from 'wasm/packages/masonry';
 import { GalleryProps, getThumbnailSize } from '../utils';
 import { MasonryWorkerAdapter } from './MasonryWorkerAdapter';
 import VirtualizedRenderer from './VirtualizedRenderer';
@@ -44,37 +44,25 @@ const MasonryRenderer = observer(({ contentRect, select, lastSelectionIndex }: G
   // note: horizontal keyboard navigation is handled elsewhere: LayoutSwitcher
   useEffect(() => {
     const onKeyDown = action((e: KeyboardEvent) => {
-      let index = lastSelectionIndex.current;
+      const index = lastSelectionIndex.current;
       if (index === undefined) {
         return;
       }
-      // Find the image that's below/above the center of the current image
-      const curTransform = worker.getTransform(index);
-      const curTransformCenter = curTransform[3] + curTransform[0] / 2;
-      const maxLookAhead = 100;
-      const numFiles = fileStore.fileList.length;
-
+      let direction;
       if (e.key === 'ArrowUp') {
-        for (let i = index - 1; i > Math.max(0, i - maxLookAhead); i--) {
-          const [tWidth, , , tLeft] = worker.getTransform(i);
-          if (tLeft < curTransformCenter && tLeft + tWidth > curTransformCenter) {
-            index = i;
-            break;
-          }
-        }
-      } else if (e.key === 'ArrowDown' && index < numFiles - 1) {
-        for (let i = index + 1; i < Math.min(i + maxLookAhead, numFiles); i++) {
-          const [tWidth, , , tLeft] = worker.getTransform(i);
-          if (tLeft < curTransformCenter && tLeft + tWidth > curTransformCenter) {
-            index = i;
-            break;
-          }
-        }
+        direction = NavigationDirection.Up;
+      } else if (e.key === 'ArrowDown') {
+        direction = NavigationDirection.Down;
       } else {
         return;
       }
+      // Find the closest image above/below the current image in the actual layout
+      const neighbor = worker.neighbor(index, direction);
+      if (neighbor === undefined) {
+        return;
+      }
       e.preventDefault();
-      select(fileStore.fileList[index], e.ctrlKey || e.metaKey, e.shiftKey);
+      select(fileStore.fileList[neighbor], e.ctrlKey || e.metaKey, e.shiftKey);
 
       // Don't change focus when TagEditor overlay is open: is closes onBlur
       if (!uiStore.isToolbarTagPopoverOpen) {
@@ -102,6 +90,9 @@ const MasonryRenderer = observer(({ contentRect, select, lastSelectionIndex }: G
             type: ViewMethodLayoutDict[viewMethod],
           },
         );
+        if (containerHeight === undefined) {
+          return;
+        }
         setContainerHeight(containerHeight);
         setLayoutTimestamp(new Date());
         setForceRerenderObj(new Date());
@@ -128,6 +119,10 @@ const MasonryRenderer = observer(({ contentRect, select, lastSelectionIndex }: G
               type: ViewMethodLayoutDict[viewMethod],
             },
           );
+          // A newer computation was started in the meantime, which updates the layout instead.
+          if (containerHeight === undefined) {
+            return;
+          }
           setContainerHeight(containerHeight);
           setLayoutTimestamp(new Date());
           // setForceRerenderObj(new Date()); // doesn't seem necessary anymore, which is nice, because it caused flickering when refetching
@@ -152,6 +147,10 @@ const MasonryRenderer = observer(({ contentRect, select, lastSelectionIndex }: G
             thumbSize: thumbnailSize,
             type: ViewMethodLayoutDict[viewMethod],
           });
+          // A newer computation was started in the meantime, which updates the layout instead.
+          if (containerHeight === undefined) {
+            return;
+          }
           setContainerHeight(containerHeight);
           setLayoutTimestamp(new Date());
           // no need for force rerender: causes flickering. Rerender already happening due to container height update anyways
@@ -2,8 +2,13 @@ import { runInAction } from 'mobx';
 
 import { ClientFile } from '../../../entities/File';
 // Force Webpack to include worker and WASM file in the build folder!
-import { MasonryType, MasonryWorker, default as init } from 'wasm/packages/masonry';
-import { ITransform, Layouter } from './layout-helpers';
+import {
+  MasonryType,
+  MasonryWorker,
+  NavigationDirection,
+  default as init,
+} from 'wasm/packages/masonry';
+import { IRange, ITransform, Layouter } from './layout-helpers';
 
 export interface MasonryOptions {
   type: MasonryType;
@@ -17,11 +22,16 @@ const defaultOpts: MasonryOptions = {
   padding: 8,
 };
 
+const emptyTransform: ITransform = [0, 0, 0, 0];
+
 export class MasonryWorkerAdapter implements Layouter {
   private worker?: MasonryWorker;
   private memory?: WebAssembly.Memory;
 
   private prevNumImgs: number = 0;
+  /** Copy of the transforms of the most recent finished computation, 4 values per image */
+  private transforms: Uint32Array = new Uint32Array(0);
+  private range: IRange = [0, 0];
 
   async initialize(numItems: number) {
     this.prevNumImgs = numItems;
@@ -37,8 +47,8 @@ export class MasonryWorkerAdapter implements Layouter {
     const worker = new Worker(new URL('wasm/packages/masonry/worker.js', import.meta.url), {
       type: 'module',
     });
-    worker.postMessage(this.memory);
     this.worker = new MasonryWorker(numItems);
+    worker.postMessage({ memory: this.memory, handle: this.worker.get_handle() });
   }
 
   async compute(
@@ -48,7 +58,8 @@ export class MasonryWorkerAdapter implements Layouter {
     opts: Partial<MasonryOptions>,
   ): Promise<number | undefined> {
     const worker = this.worker;
-    if (worker === undefined) {
+    const memory = this.memory;
+    if (worker === undefined || memory === undefined) {
       return Promise.reject('Worker is uninitialized.');
     }
 
@@ -57,18 +68,29 @@ export class MasonryWorkerAdapter implements Layouter {
     }
 
     this.prevNumImgs = numImgs;
+    // Write all dimensions into the buffer in the WebAssembly memory at once instead of calling
+    // set_dimension for every image. The pointer is requested first, as it may grow the memory.
+    const ptr = worker.dimensions_ptr();
+    const dimensions = new Uint16Array(memory.buffer, ptr, numImgs * 2);
     runInAction(() => {
-      for (let i = 0; i < imgs.length; i++) {
-        worker.set_dimension(i, imgs[i].width, imgs[i].height);
+      for (let i = 0; i < Math.min(imgs.length, numImgs); i++) {
+        dimensions[i * 2] = imgs[i].width;
+        dimensions[i * 2 + 1] = imgs[i].height;
       }
     });
+    worker.flush_dimensions();
 
-    await worker.compute(
+    const status = await worker.compute(
       containerWidth,
       opts.type || defaultOpts.type,
       opts.thumbSize || defaultOpts.thumbSize,
       opts.padding || defaultOpts.padding,
     );
+    // The newer computation copies the transforms and provides the height once it is done.
+    if (status === 'superseded') {
+      return undefined;
+    }
+    this.copyTransforms(worker, numImgs);
     return worker.get_height();
   }
 
@@ -76,24 +98,79 @@ export class MasonryWorkerAdapter implements Layouter {
     containerWidth: number,
     opts: Partial<MasonryOptions>,
   ): Promise<number | undefined> {
-    if (this.worker === undefined) {
+    const worker = this.worker;
+    if (worker === undefined) {
       return Promise.reject('Worker is uninitialized.');
     }
-    await this.worker.compute(
+    const status = await worker.compute(
       containerWidth,
       opts.type || defaultOpts.type,
       opts.thumbSize || defaultOpts.thumbSize,
       opts.padding || defaultOpts.padding,
     );
-    return this.worker.get_height();
+    if (status === 'superseded') {
+      return undefined;
+    }
+    this.copyTransforms(worker, this.prevNumImgs);
+    return worker.get_height();
   }
 
   // This method will be available in the custom VirtualizedRenderer component as layout.getItemLayout
+  // The transforms of the last finished computation are returned while the next one is running.
   getTransform(index: number): ITransform {
     if (this.worker === undefined || this.memory === undefined) {
       throw new Error('Worker is uninitialized.');
     }
-    const ptr = this.worker.get_transform(index);
-    return new Uint32Array(this.memory.buffer, ptr, 4) as unknown as ITransform;
+    if (index * 4 >= this.transforms.length) {
+      return emptyTransform;
+    }
+    return this.transforms.subarray(index * 4, index * 4 + 4) as unknown as ITransform;
+  }
+
+  // The range of the last finished computation is returned while the next one is running.
+  itemsInRange(top: number, bottom: number): IRange {
+    if (this.worker === undefined || this.memory === undefined) {
+      throw new Error('Worker is uninitialized.');
+    }
+    try {
+      const ptr = this.worker.items_in_range(Math.max(0, top), Math.max(0, bottom));
+      const [start, end] = new Uint32Array(this.memory.buffer, ptr, 2);
+      this.range = [start, end];
+    } catch (e) {
+      // Throws while a computation is running.
+    }
+    return this.range;
+  }
+
+  /**
+   * Returns the index of the closest image in the direction of the image at the index, or
+   * undefined if there is none or a computation is running.
+   */
+  neighbor(index: number, direction: NavigationDirection): number | undefined {
+    if (this.worker === undefined) {
+      throw new Error('Worker is uninitialized.');
+    }
+    try {
+      return this.worker.neighbor(index, direction);
+    } catch (e) {
+      // Throws while a computation is running.
+      return undefined;
+    }
+  }
+
+  private copyTransforms(worker: MasonryWorker, numImgs: number) {
+    if (this.memory === undefined) {
+      return;
+    }
+    if (numImgs === 0) {
+      this.transforms = new Uint32Array(0);
+      return;
+    }
+    try {
+      const ptr = worker.get_transform(0);
+      this.transforms = new Uint32Array(this.memory.buffer, ptr, numImgs * 4).slice();
+    } catch (e) {
+      // Throws while a newer computation is running, which copies the transforms once it is done.
+    }
   }
 }
@@ -8,7 +8,7 @@ import { useStore } from '../../../contexts/StoreContext';
 import { ClientFile } from '../../../entities/File';
 import useMountState from '../../../hooks/useMountState';
 import { MasonryCell } from '../GalleryItem';
-import { Layouter, findViewportEdge } from './layout-helpers';
+import { Layouter } from './layout-helpers';
 
 interface IRendererProps {
   containerHeight: number;
@@ -58,16 +58,21 @@ const VirtualizedRenderer = observer(
         const yOffset = viewport?.scrollTop || 0;
         const viewportHeight = viewport?.clientHeight || 0;
 
-        const firstImageIndex = findViewportEdge(yOffset, numImages, layout);
-        const start = findViewportEdge(yOffset - overdraw, numImages, layout);
-        const end = findViewportEdge(yOffset + viewportHeight + overdraw, numImages, layout);
+        const [firstImageIndex, lastImageIndex] = layout.itemsInRange(
+          yOffset,
+          yOffset + viewportHeight,
+        );
+        const [start, end] = layout.itemsInRange(
+          yOffset - overdraw,
+          yOffset + viewportHeight + overdraw,
+        );
 
         setStartRenderIndex(start);
         // hard limit of 512 images at once, for safety reasons (we don't want any exploding computers). Might be bad for people with 4k screens and small thumbnails...
         setEndRenderIndex(Math.min(end, start + 512));
 
         // store the first item in the viewport in the UIStore so that switching between view modes retains the scroll position
-        if (setFirstItem) {
+        if (setFirstItem && firstImageIndex < lastImageIndex) {
           uiStore.setFirstItem(firstImageIndex);
         }
       },
@@ -163,7 +168,7 @@ const VirtualizedRenderer = observer(
       <div className={className} onScroll={handleScroll} ref={wrapperRef}>
         {/* One div for the content */}
         <div style={{ width: containerWidth, height: containerHeight }}>
-          {images.slice(startRenderIndex, endRenderIndex + 1).map((im, index) => {
+          {images.slice(startRenderIndex, endRenderIndex).map((im, index) => {
             const fileListIndex = startRenderIndex + index;
             const transform = layout.getTransform(fileListIndex);
             return (
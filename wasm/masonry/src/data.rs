@@ -1,46 +1,209 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::AtomicI32;
 use wasm_bindgen::prelude::*;
 
 use crate::layout::Layout;
+use crate::sync::SharedLayout;
 
 pub struct Computation {
-    pub width: u16,
+    pub width: u32,
     pub config: MasonryConfig,
-    pub layout_ptr: *mut Layout,
+    pub layout: Arc<SharedLayout>,
+    /// Changes to the layout that were made while it was locked by a previous computation.
+    pub commands: Vec<Command>,
+    pub generation: i32,
+    /// Whether the computation is pending, finished or superseded, which is shared with the
+    /// thread waiting for it (see [`crate::sync::send_computation()`]).
+    pub status: Arc<AtomicI32>,
+}
+
+/// A change to the layout that is applied as soon as it is not locked anymore.
+pub enum Command {
+    Resize(usize),
+    SetDimension(usize, u16, u16),
+    SetDimensions(Vec<u16>),
+    SetSpan(usize, u8, u8),
+    SetAnchor(usize, i32),
+    SetPosition(usize, Option<[u32; 4]>),
+    AddGroup(usize, u16),
+    ClearGroups,
 }
 
 #[wasm_bindgen]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum MasonryType {
     Vertical,
     Horizontal,
     Grid,
+    Justified,
+    /// Vertical masonry layout that places the items in reading order from left to right. A column
+    /// is only skipped if it is more than one column width taller than the shortest one.
+    VerticalOrdered,
+    /// Vertical masonry layout that places every row of as many items as there are columns
+    /// together, with the highest item in the shortest column.
+    VerticalBalanced,
+    /// Layout where the items keep the positions that were set for them and all other items are
+    /// placed at the lowest free spot at the thumbnail size, like a moodboard.
+    Freeform,
+}
+
+/// Alignment of the last row of a horizontal or justified layout if it does not fill the container
+/// width.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum LastRow {
+    /// Keeps the items at the thumbnail size aligned to the left.
+    Keep,
+    /// Scales the items so that they fill the container width.
+    Justify,
+    /// Keeps the items at the thumbnail size in the center.
+    Center,
+    /// Moves items from the previous row into the last row, so that both rows have about the same
+    /// width.
+    Balance,
+}
+
+/// Direction in which the items are placed.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// Rows are filled from the left to the right and the container scrolls vertically.
+    LeftToRight,
+    /// Rows are filled from the right to the left and the container scrolls vertically.
+    RightToLeft,
+    /// Columns are filled from the top to the bottom and the container scrolls horizontally.
+    ///
+    /// Every layout is transposed: the container width becomes the height of the container and
+    /// the returned height becomes its width.
+    TopToBottom,
+}
+
+/// Direction on the screen in which the next item is searched when navigating with the keyboard.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum NavigationDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// How the number and width of the columns of a vertical or grid layout are chosen.
+///
+/// The columns never exceed the container width. If they are narrower in total, the space after
+/// the last column stays empty.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColumnMode {
+    /// As many columns as fit at about the thumbnail size, which fill the container width.
+    Auto,
+    /// A fixed number of columns that are as wide as the thumbnail size, or narrower if the
+    /// container is too narrow.
+    Fixed,
+    /// A fixed number of columns that fill the container width.
+    Fit,
+    /// As many columns as fit at a minimum width, which are at most as wide as a maximum width.
+    Width,
 }
 
+#[derive(Clone, Copy)]
 pub struct MasonryConfig {
     pub kind: MasonryType,
-    pub thumbnail_size: u16,
+    pub thumbnail_size: u32,
     pub padding: u16,
+    /// Gap between the items next to each other or `None` to use the padding.
+    pub column_gap: Option<u16>,
+    /// Gap between the items below each other or `None` to use the padding.
+    pub row_gap: Option<u16>,
+    pub inset: u16,
+    /// Size of the grid to which the positions of the freeform layout are rounded or 0.
+    pub snap: u16,
+    pub caption_height: u16,
+    /// Column mode with its column count or minimum width and its maximum width.
+    pub column_mode: (ColumnMode, u32, u32),
+    pub retain_previous: bool,
+    pub max_row_deviation: u8,
+    pub last_row: LastRow,
+    pub direction: Direction,
+    pub max_aspect_ratio: f32,
 }
 
 impl MasonryConfig {
-    pub const DEFAULT_THUMBNAIL_SIZE: u16 = 300;
+    pub const DEFAULT_THUMBNAIL_SIZE: u32 = 300;
     pub const DEFAULT_PADDING: u16 = 8;
+    pub const DEFAULT_MAX_ROW_DEVIATION: u8 = 25;
+    pub const DEFAULT_MAX_ASPECT_RATIO: f32 = 3.0;
 
-    pub fn new(kind: MasonryType, thumbnail_size: u16, padding: u16) -> MasonryConfig {
+    pub fn new(kind: MasonryType, thumbnail_size: u32, padding: u16) -> MasonryConfig {
         MasonryConfig {
             kind,
             thumbnail_size,
             padding,
+            column_gap: None,
+            row_gap: None,
+            inset: 0,
+            snap: 0,
+            caption_height: 0,
+            column_mode: (ColumnMode::Auto, 0, 0),
+            retain_previous: false,
+            max_row_deviation: MasonryConfig::DEFAULT_MAX_ROW_DEVIATION,
+            last_row: LastRow::Keep,
+            direction: Direction::LeftToRight,
+            max_aspect_ratio: MasonryConfig::DEFAULT_MAX_ASPECT_RATIO,
         }
     }
+
+    /// Applies all settings except the kind to the layout.
+    pub fn apply(&self, layout: &mut Layout) {
+        layout.set_thumbnail_size(self.thumbnail_size);
+        layout.set_gaps(
+            self.column_gap.unwrap_or(self.padding),
+            self.row_gap.unwrap_or(self.padding),
+        );
+        layout.set_inset(self.inset);
+        layout.set_snap(self.snap);
+        layout.set_caption_height(self.caption_height);
+        let (column_mode, first, second) = self.column_mode;
+        layout.set_column_mode(column_mode, first, second);
+        layout.set_retain_previous(self.retain_previous);
+        layout.set_max_row_deviation(self.max_row_deviation);
+        layout.set_last_row(self.last_row);
+        layout.set_direction(self.direction);
+        layout.set_max_aspect_ratio(self.max_aspect_ratio);
+    }
 }
 
 impl Computation {
-    pub fn new(width: u16, config: MasonryConfig, layout: &mut Layout) -> Computation {
+    pub fn new(
+        width: u32,
+        config: MasonryConfig,
+        layout: Arc<SharedLayout>,
+        commands: Vec<Command>,
+    ) -> Computation {
         Computation {
             width,
             config,
-            layout_ptr: layout as _,
+            layout,
+            commands,
+            generation: 0,
+            status: Arc::default(),
+        }
+    }
+}
+
+impl Command {
+    pub fn apply(self, layout: &mut Layout) {
+        match self {
+            Command::Resize(new_len) => layout.resize(new_len),
+            Command::SetDimension(index, src_width, src_height) => {
+                layout.set_dimension(index, src_width, src_height);
+            }
+            Command::SetDimensions(dimensions) => layout.set_dimensions(&dimensions),
+            Command::SetSpan(index, columns, rows) => layout.set_span(index, columns, rows),
+            Command::SetAnchor(index, offset) => layout.set_anchor(index, offset),
+            Command::SetPosition(index, position) => layout.set_position(index, position),
+            Command::AddGroup(start, header_height) => layout.add_group(start, header_height),
+            Command::ClearGroups => layout.clear_groups(),
         }
     }
 }
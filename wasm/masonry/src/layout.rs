@@ -1,41 +1,194 @@
 // Main idea of this package:
 // - Take in a list of image dimensions, and a base thumbnail size (e.g. S, M, L)
 // - Output a list of image positions, laid out in a masonry format
-// TODO: Could also use the google photos layout: Groups of masonry layouts, each with a header (e.g. the date)
+// - Optionally split the items into groups, each with a header (e.g. the date) like google photos
 use crate::util::UnwrapOrAbort;
 use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+use core::ops::Range;
 
+use crate::data::{ColumnMode, Direction, LastRow, MasonryType, NavigationDirection};
 use crate::packed::{F32x4, U32x4};
+use snapshot::{Reader, Writer};
+use spatial_index::SpatialIndex;
 
 pub struct Layout {
     num_items: usize,
     transforms: Vec<Transform>,
     aspect_ratios: Vec<AspectRatio>,
-    thumbnail_size: u16,
-    padding: u16,
+    /// Number of columns and rows every item covers in the grid and vertical masonry layouts.
+    spans: Vec<Span>,
+    /// Supplied positions of the items in the freeform layout or `None` for items that are placed
+    /// automatically. Only grows as far as positions are set.
+    positions: Vec<Option<Position>>,
+    groups: Vec<Group>,
+    header_transforms: Vec<Transform>,
+    index: SpatialIndex,
+    /// Cost and start of the best row ending before the item for the justified layout.
+    row_breaks: Vec<(f32, usize)>,
+    /// Kind and container width of the most recent computation.
+    computed: Option<(MasonryType, u32)>,
+    /// Index of the first item whose transform is out of date or `None` if all are up to date.
+    dirty: Option<usize>,
+    height: u32,
+    thumbnail_size: u32,
+    /// Space between the items next to each other.
+    column_gap: u16,
+    /// Space between the items below each other and between the groups.
+    row_gap: u16,
+    /// Space between the container edges and the items.
+    inset: u16,
+    /// Size of the grid to which the supplied positions of the freeform layout are rounded or 0.
+    snap: u16,
+    /// Space for a caption below the image of every item, which is part of its height.
+    caption_height: u16,
+    /// Column mode of the vertical and grid layouts with its column count or minimum width and its
+    /// maximum width.
+    column_mode: (ColumnMode, u32, u32),
+    max_row_deviation: u8,
+    last_row: LastRow,
+    direction: Direction,
+    /// Minimum length of the shorter side of every aspect ratio, which crops items with extreme
+    /// aspect ratios.
+    min_aspect_side: u16,
+    /// How the transforms were converted from the left to right layout after the most recent
+    /// computation or `None` if they were not converted.
+    mapped: Option<Mapping>,
+    /// Number of items of the most recent finished computation or `None` if it was cancelled.
+    laid_out: Option<usize>,
+    /// Whether the transforms are copied to `previous_transforms` before they are computed again.
+    retain_previous: bool,
+    /// Transforms of the items as they were before the most recent computation.
+    previous_transforms: Vec<Transform>,
+    /// Index of the item and its offset from the top of the viewport, which the next finished
+    /// computation keeps in place.
+    anchor: Option<(usize, i32)>,
+    /// Scroll position that keeps the anchor in place after the most recent computation.
+    scroll_position: Option<u32>,
 }
 
 #[repr(transparent)]
 #[derive(Clone, Default)]
 pub struct Transform(U32x4);
 
-#[derive(Clone, Default)]
+/// Aspect ratio of an item as fixed point numbers, where the longer side is
+/// [`AspectRatio::SCALE`].
+#[derive(Clone, PartialEq)]
 struct AspectRatio {
-    width: u8,
-    height: u8,
+    width: u16,
+    height: u16,
+}
+
+impl Default for AspectRatio {
+    fn default() -> Self {
+        AspectRatio {
+            width: AspectRatio::SCALE,
+            height: AspectRatio::SCALE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Span {
+    columns: u8,
+    rows: u8,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span {
+            columns: 1,
+            rows: 1,
+        }
+    }
+}
+
+/// Position and size of an item in the freeform layout relative to the top of its group.
+#[derive(Clone, Copy, PartialEq)]
+struct Position {
+    width: u32,
+    height: u32,
+    top: u32,
+    left: u32,
+}
+
+/// A group starts at the item index and ends where the next group starts.
+struct Group {
+    start: usize,
+    header_height: u16,
+}
+
+/// Strategy with which the vertical masonry layouts choose the column of every item.
+#[derive(Clone, Copy, PartialEq)]
+enum Placement {
+    /// The shortest column.
+    Shortest,
+    /// The column right of the previous item unless it is much taller than the shortest column.
+    Ordered,
+    /// The shortest column for the highest remaining item of every row.
+    Balanced,
+}
+
+/// Direction, container width and inset with which the first items and headers were converted.
+#[derive(Clone, Copy)]
+struct Mapping {
+    direction: Direction,
+    container_width: u32,
+    inset: u32,
+    num_items: usize,
+    num_headers: usize,
+}
+
+/// Function that lays out the items of a group, see [`Layout::compute_groups()`].
+type LayoutGroup = fn(&mut Layout, Range<usize>, usize, u32, u32, &dyn Fn() -> bool) -> Option<u32>;
+
+/// Contents of a snapshot that were read and validated by [`Layout::read_snapshot()`].
+pub struct Snapshot {
+    kind: MasonryType,
+    container_width: u32,
+    height: u32,
+    items: Vec<(AspectRatio, Span, Transform)>,
+    groups: Vec<Group>,
+    header_transforms: Vec<Transform>,
+    positions: Vec<Option<Position>>,
+    row_breaks: Vec<(f32, usize)>,
 }
 
 const MIN_ITEMS_CAPACITY: usize = 1_000;
 
 impl Layout {
-    pub fn new(num_items: usize, thumbnail_size: u16, padding: u16) -> Layout {
+    pub fn new(num_items: usize, thumbnail_size: u32, padding: u16) -> Layout {
         let capacity = num_items.max(MIN_ITEMS_CAPACITY);
         Layout {
             num_items,
             transforms: vec![Transform::default(); capacity],
             aspect_ratios: vec![AspectRatio::default(); capacity],
+            spans: vec![Span::default(); capacity],
+            positions: Vec::new(),
+            groups: Vec::new(),
+            header_transforms: Vec::new(),
+            index: SpatialIndex::default(),
+            row_breaks: Vec::new(),
+            computed: None,
+            dirty: Some(0),
+            height: 0,
             thumbnail_size,
-            padding,
+            column_gap: padding,
+            row_gap: padding,
+            inset: 0,
+            snap: 0,
+            caption_height: 0,
+            column_mode: (ColumnMode::Auto, 0, 0),
+            max_row_deviation: 0,
+            last_row: LastRow::Keep,
+            direction: Direction::LeftToRight,
+            min_aspect_side: AspectRatio::SCALE / 3,
+            mapped: None,
+            laid_out: None,
+            retain_previous: false,
+            previous_transforms: Vec::new(),
+            anchor: None,
+            scroll_position: None,
         }
     }
 
@@ -43,29 +196,537 @@ impl Layout {
         self.transforms.get(index)
     }
 
+    /// Returns the transform of the item before the most recent computation or `None` if the item
+    /// did not exist then or previous transforms are not retained.
+    pub fn get_previous_transform(&self, index: usize) -> Option<&Transform> {
+        self.previous_transforms.get(index)
+    }
+
+    /// Returns the transform of the item linearly interpolated between its previous and current
+    /// transform, where `t` is between 0 (previous) and 1 (current). Items without a previous
+    /// transform do not move.
+    pub fn interpolate(&self, index: usize, t: f32) -> Option<Transform> {
+        let end = self.transforms.get(..self.num_items)?.get(index)?.0;
+        let start = self
+            .get_previous_transform(index)
+            .map_or(end, |start| start.0);
+        // The endpoints are returned exactly, as large coordinates do not survive the conversion.
+        if t <= 0.0 {
+            return Some(Transform(start));
+        } else if t >= 1.0 {
+            return Some(Transform(end));
+        }
+        let interpolated = F32x4::from(start) * F32x4::from(1.0 - t)
+            + F32x4::from(end) * F32x4::from(t)
+            + F32x4::from(0.5);
+        Some(Transform(U32x4::from(interpolated)))
+    }
+
+    pub fn get_header_transform(&self, index: usize) -> Option<&Transform> {
+        self.header_transforms.get(index)
+    }
+
+    /// Returns the scroll position at which the anchor set before the most recent computation is
+    /// at the same offset in the viewport as before, or `None` if no anchor was set.
+    ///
+    /// The position is never negative. With [`Direction::TopToBottom`] it is horizontal.
+    pub fn get_scroll_position(&self) -> Option<u32> {
+        self.scroll_position
+    }
+
+    pub fn num_items(&self) -> usize {
+        self.num_items
+    }
+
+    /// Serializes the most recent computation into a compact binary snapshot, which
+    /// [`Layout::read_snapshot()`] can load without computing the layout again.
+    ///
+    /// The snapshot contains the aspect ratios, spans, groups and positions of the items and the
+    /// resulting transforms. Returns `None` if the layout was changed since it was computed.
+    pub fn snapshot(&self) -> Option<Vec<u8>> {
+        let (kind, container_width) = self.computed?;
+        if self.dirty.is_some() || self.laid_out != Some(self.num_items) {
+            return None;
+        }
+        let mut snapshot = Writer(self.snapshot_key(kind, container_width));
+        snapshot.u32(self.height);
+        snapshot.u32(u32::try_from(self.num_items).ok()?);
+        for index in 0..self.num_items {
+            let aspect_ratio = &self.aspect_ratios[index];
+            snapshot.u16(aspect_ratio.width);
+            snapshot.u16(aspect_ratio.height);
+            snapshot.u8(self.spans[index].columns);
+            snapshot.u8(self.spans[index].rows);
+            snapshot.transform(&self.transforms[index]);
+        }
+        snapshot.u32(u32::try_from(self.groups.len()).ok()?);
+        for (group, header) in self.groups.iter().zip(&self.header_transforms) {
+            snapshot.u32(u32::try_from(group.start).ok()?);
+            snapshot.u16(group.header_height);
+            snapshot.transform(header);
+        }
+        let positions = &self.positions[..self.positions.len().min(self.num_items)];
+        snapshot.u32(u32::try_from(positions.len()).ok()?);
+        for position in positions {
+            match position {
+                Some(position) => {
+                    snapshot.u8(1);
+                    snapshot.u32(position.width);
+                    snapshot.u32(position.height);
+                    snapshot.u32(position.top);
+                    snapshot.u32(position.left);
+                }
+                None => snapshot.u8(0),
+            }
+        }
+        // The best rows of the justified layout are needed to compute it again incrementally.
+        let row_breaks = match kind {
+            MasonryType::Justified => {
+                &self.row_breaks[..self.row_breaks.len().min(self.num_items + 1)]
+            }
+            _ => &[],
+        };
+        snapshot.u32(u32::try_from(row_breaks.len()).ok()?);
+        for &(cost, start) in row_breaks {
+            snapshot.u32(cost.to_bits());
+            snapshot.u32(u32::try_from(start).ok()?);
+        }
+        Some(snapshot.0)
+    }
+
+    /// Reads a snapshot for [`Layout::apply_snapshot()`] without changing the layout.
+    ///
+    /// Returns `None` if the snapshot was not taken with the same kind, container width and
+    /// settings as this layout has or if it is malformed.
+    pub fn read_snapshot(
+        &self,
+        snapshot: &[u8],
+        kind: MasonryType,
+        container_width: u32,
+    ) -> Option<Snapshot> {
+        let key = self.snapshot_key(kind, container_width);
+        let mut reader = Reader(snapshot.strip_prefix(key.as_slice())?);
+        let height = reader.u32()?;
+        let items = Snapshot::read_items(&mut reader)?;
+        let (groups, header_transforms) = Snapshot::read_groups(&mut reader, items.len())?;
+        let positions = Snapshot::read_positions(&mut reader, items.len())?;
+        let row_breaks = Snapshot::read_row_breaks(&mut reader, items.len())?;
+        let snapshot = Snapshot {
+            kind,
+            container_width,
+            height,
+            items,
+            groups,
+            header_transforms,
+            positions,
+            row_breaks,
+        };
+        (reader.0.is_empty() && snapshot.rows_stay_in_groups()).then(|| snapshot)
+    }
+
+    /// Replaces the items and groups with the ones of a snapshot that was read with the settings
+    /// of this layout and returns the height of the container, as if the layout was computed with
+    /// the kind and container width of the snapshot.
+    ///
+    /// Setting the same dimensions, spans and positions afterwards does not change the layout, so
+    /// the next computation does not have to compute anything.
+    pub fn apply_snapshot(&mut self, snapshot: Snapshot) -> u32 {
+        let num_items = snapshot.items.len();
+        self.resize(num_items);
+        for (index, (aspect_ratio, span, transform)) in snapshot.items.into_iter().enumerate() {
+            self.aspect_ratios[index] = aspect_ratio;
+            self.spans[index] = span;
+            self.transforms[index] = transform;
+        }
+        self.groups = snapshot.groups;
+        self.header_transforms = snapshot.header_transforms;
+        self.positions = snapshot.positions;
+        self.row_breaks = snapshot.row_breaks;
+        self.computed = Some((snapshot.kind, snapshot.container_width));
+        self.dirty = None;
+
+        // The transforms were stored as they were converted, so they are converted back first.
+        let container_width = snapshot
+            .container_width
+            .saturating_sub(2 * u32::from(self.inset));
+        if let Some(mapping) = self.mapping(container_width) {
+            self.map_direction(mapping, true);
+        }
+        self.mapped = None;
+        self.finish(
+            container_width,
+            snapshot.height.saturating_sub(2 * u32::from(self.inset)),
+        );
+        self.laid_out = Some(num_items);
+        self.resolve_anchor();
+        self.height
+    }
+
+    /// Returns the smallest range of item indices that contains all items which intersect with the
+    /// vertical range between top and bottom (exclusive) of the most recent computation.
+    ///
+    /// With [`Direction::TopToBottom`] the range is horizontal instead.
+    pub fn items_in_range(&self, top: u32, bottom: u32) -> Range<usize> {
+        // The index is built before the transforms are moved by the inset.
+        let inset = self.mapped.map_or(0, |mapping| mapping.inset);
+        let mut items = self
+            .index
+            .query(top.saturating_sub(inset), bottom.saturating_sub(inset));
+        let transposed = self.direction == Direction::TopToBottom;
+        let intersects = |index: usize| {
+            let transform = &self.transforms[index].0;
+            let (item_top, item_height) = if transposed {
+                (transform.get::<3>(), transform.get::<0>())
+            } else {
+                (transform.get::<2>(), transform.get::<1>())
+            };
+            item_top < bottom && item_top.saturating_add(item_height) > top
+        };
+        // Only the bands are exact, so the range can contain items at the edges that are outside.
+        while !items.is_empty() && !intersects(items.start) {
+            items.start += 1;
+        }
+        while !items.is_empty() && !intersects(items.end - 1) {
+            items.end -= 1;
+        }
+        items
+    }
+
+    /// Returns the indices of all items of the most recent computation which intersect with the
+    /// rectangle at x and y with the given width and height, in ascending order.
+    pub fn items_in_rect(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let (right, bottom) = (x.saturating_add(width), y.saturating_add(height));
+        let range = if self.direction == Direction::TopToBottom {
+            self.items_in_range(x, right)
+        } else {
+            self.items_in_range(y, bottom)
+        };
+        range.filter(move |&index| {
+            let [item_width, item_height, item_top, item_left] =
+                self.transforms[index].0.to_array();
+            item_left < right
+                && item_left.saturating_add(item_width) > x
+                && item_top < bottom
+                && item_top.saturating_add(item_height) > y
+        })
+    }
+
+    /// Returns the index of the item at x and y of the most recent computation or `None` if there
+    /// is no item.
+    pub fn item_at(&self, x: u32, y: u32) -> Option<usize> {
+        self.items_in_rect(x, y, 1, 1).next()
+    }
+
+    /// Returns the index of the nearest item in the direction on the screen from the item at the
+    /// given index or `None` if there is none.
+    ///
+    /// Only items completely beyond the edge of the item in the direction are considered, and items
+    /// without an area are skipped. Items that overlap with the item on the other axis are preferred
+    /// over those that are diagonal to it. Then the item with the nearest edge wins, and then the
+    /// one whose center is closest on the other axis.
+    pub fn neighbor(&self, index: usize, direction: NavigationDirection) -> Option<usize> {
+        let transforms = self.transforms.get(..self.num_items)?;
+        // Start and end on the axis of the direction and on the other axis, mirrored for up and
+        // left, so that the neighbors always have greater positions.
+        let edges = |transform: &Transform| {
+            let [width, height, top, left] = transform.0.to_array().map(i64::from);
+            match direction {
+                NavigationDirection::Up => [-top - height, -top, left, left + width],
+                NavigationDirection::Down => [top, top + height, left, left + width],
+                NavigationDirection::Left => [-left - width, -left, top, top + height],
+                NavigationDirection::Right => [left, left + width, top, top + height],
+            }
+        };
+        let [_, end, cross_start, cross_end] = edges(transforms.get(index)?);
+        transforms
+            .iter()
+            .enumerate()
+            .filter(|&(other, transform)| {
+                other != index && transform.0.get::<0>() > 0 && transform.0.get::<1>() > 0
+            })
+            .filter_map(|(other, transform)| {
+                let [other_start, _, other_cross_start, other_cross_end] = edges(transform);
+                if other_start < end {
+                    return None;
+                }
+                let cross_gap =
+                    (other_cross_start.max(cross_start) - other_cross_end.min(cross_end)).max(0);
+                let center_distance =
+                    (other_cross_start + other_cross_end - cross_start - cross_end).abs();
+                Some((
+                    (
+                        cross_gap > 0,
+                        other_start - end + cross_gap,
+                        center_distance,
+                    ),
+                    other,
+                ))
+            })
+            .min_by_key(|&(key, _)| key)
+            .map(|(_, other)| other)
+    }
+
     pub fn set_dimension(&mut self, index: usize, src_width: u16, src_height: u16) {
         if let Some(aspect_ratio) = self.aspect_ratios.get_mut(index) {
+            let previous = aspect_ratio.clone();
             aspect_ratio.set(src_width, src_height);
+            if *aspect_ratio != previous {
+                self.invalidate(index);
+            }
+        }
+    }
+
+    /// Sets the dimensions of the items from the first item onwards.
+    ///
+    /// The dimensions are width and height pairs after each other. Four aspect ratios are
+    /// corrected at once.
+    pub fn set_dimensions(&mut self, dimensions: &[u16]) {
+        let len = (dimensions.len() / 2).min(self.aspect_ratios.len());
+        let (chunks, rest) = dimensions[..len * 2].split_at(len / 4 * 8);
+
+        let mut first_changed = None;
+        for (chunk_index, chunk) in chunks.chunks_exact(8).enumerate() {
+            let [w0, h0, w1, h1, w2, h2, w3, h3]: [u16; 8] = chunk.try_into().unwrap_or_abort();
+            let (widths, heights) = correct_aspect_ratios(
+                U32x4::new(w0.into(), w1.into(), w2.into(), w3.into()),
+                U32x4::new(h0.into(), h1.into(), h2.into(), h3.into()),
+            );
+
+            let start = chunk_index * 4;
+            for ((index, width), height) in (start..start + 4)
+                .zip(widths.to_array())
+                .zip(heights.to_array())
+            {
+                let aspect_ratio = AspectRatio {
+                    width: u16::try_from(width).unwrap_or_abort(),
+                    height: u16::try_from(height).unwrap_or_abort(),
+                };
+                if self.aspect_ratios[index] != aspect_ratio {
+                    self.aspect_ratios[index] = aspect_ratio;
+                    first_changed.get_or_insert(index);
+                }
+            }
+        }
+        if let Some(index) = first_changed {
+            self.invalidate(index);
+        }
+
+        let start = chunks.len() / 2;
+        for (index, dimension) in (start..).zip(rest.chunks_exact(2)) {
+            self.set_dimension(index, dimension[0], dimension[1]);
+        }
+    }
+
+    /// Sets the number of columns and rows the item covers.
+    ///
+    /// Spans are only used by the grid and vertical masonry layouts. In the vertical masonry
+    /// layout the number of rows is ignored because the height follows from the aspect ratio.
+    pub fn set_span(&mut self, index: usize, columns: u8, rows: u8) {
+        if index >= self.num_items {
+            return;
+        }
+        if let Some(span) = self.spans.get_mut(index) {
+            let new_span = Span {
+                columns: columns.max(1),
+                rows: rows.max(1),
+            };
+            if *span != new_span {
+                *span = new_span;
+                // Groups with spans are laid out differently, so the items in front can move too.
+                self.invalidate(self.group_start(index));
+            }
+        }
+    }
+
+    /// Sets the width, height, top and left offset of the item in the freeform layout or lets the
+    /// layout place it if `position` is `None`.
+    ///
+    /// The offsets are relative to the top of the group below its header and in the coordinates of
+    /// [`Direction::LeftToRight`] without the inset.
+    pub fn set_position(&mut self, index: usize, position: Option<[u32; 4]>) {
+        if index >= self.num_items {
+            return;
+        }
+        let position = position.map(|[width, height, top, left]| Position {
+            width,
+            height,
+            top,
+            left,
+        });
+        if self.positions.get(index).copied().flatten() != position {
+            if self.positions.len() <= index {
+                self.positions.resize(index + 1, None);
+            }
+            self.positions[index] = position;
+            // The items placed automatically avoid the supplied ones in the whole group.
+            self.invalidate(self.group_start(index));
+        }
+    }
+
+    /// Sets the item that stays in place during the next finished computation and its offset from
+    /// the top of the viewport, which is negative if the item starts above the viewport.
+    pub fn set_anchor(&mut self, index: usize, offset: i32) {
+        self.anchor = Some((index, offset));
+    }
+
+    /// Sets whether the transforms from before a computation are kept, which makes it possible to
+    /// animate between the previous and the current layout.
+    pub fn set_retain_previous(&mut self, retain_previous: bool) {
+        self.retain_previous = retain_previous;
+        if !retain_previous {
+            self.previous_transforms = Vec::new();
+        }
+    }
+
+    pub fn set_thumbnail_size(&mut self, thumbnail_size: u32) {
+        if self.thumbnail_size != thumbnail_size {
+            self.thumbnail_size = thumbnail_size;
+            self.invalidate(0);
+        }
+    }
+
+    pub fn set_gaps(&mut self, column_gap: u16, row_gap: u16) {
+        if (self.column_gap, self.row_gap) != (column_gap, row_gap) {
+            self.column_gap = column_gap;
+            self.row_gap = row_gap;
+            self.invalidate(0);
+        }
+    }
+
+    /// Sets the space between the container edges and the items, which is included in the
+    /// container width and height.
+    pub fn set_inset(&mut self, inset: u16) {
+        if self.inset != inset {
+            self.inset = inset;
+            self.invalidate(0);
+        }
+    }
+
+    pub fn set_snap(&mut self, snap: u16) {
+        if self.snap != snap {
+            self.snap = snap;
+            self.invalidate(0);
+        }
+    }
+
+    /// Sets the height that is added below the image of every item that is placed by the layout.
+    ///
+    /// The image keeps its aspect ratio and ends the caption height above the bottom of the
+    /// transform. With [`Direction::TopToBottom`] the caption is right of the image instead.
+    pub fn set_caption_height(&mut self, caption_height: u16) {
+        if self.caption_height != caption_height {
+            self.caption_height = caption_height;
+            self.invalidate(0);
+        }
+    }
+
+    pub fn set_column_mode(&mut self, mode: ColumnMode, first: u32, second: u32) {
+        if self.column_mode != (mode, first, second) {
+            self.column_mode = (mode, first, second);
+            self.invalidate(0);
+        }
+    }
+
+    /// Sets how much the height of a row may deviate from the thumbnail size in percent in the
+    /// justified layout and when balancing the last row.
+    pub fn set_max_row_deviation(&mut self, max_row_deviation: u8) {
+        if self.max_row_deviation != max_row_deviation {
+            self.max_row_deviation = max_row_deviation;
+            self.invalidate(0);
+        }
+    }
+
+    pub fn set_last_row(&mut self, last_row: LastRow) {
+        if self.last_row != last_row {
+            self.last_row = last_row;
+            self.invalidate(0);
         }
     }
 
-    pub fn set_thumbnail_size(&mut self, thumbnail_size: u16) {
-        // The reason for this limitation is the way how the thumbnail size is calculated for the
-        // vertical and horizontal masonry layout.
-        const MAX_THUMBNAIL_SIZE: u16 = u16::MAX / 100;
-        self.thumbnail_size = thumbnail_size.min(MAX_THUMBNAIL_SIZE);
+    /// Sets how many times as wide as high or vice versa an item can be at most. Items with more
+    /// extreme aspect ratios are cropped. A ratio of 0 disables the limit.
+    pub fn set_max_aspect_ratio(&mut self, max_aspect_ratio: f32) {
+        let min_aspect_side = if max_aspect_ratio > 0.0 {
+            let side = round_down(f32::from(AspectRatio::SCALE) / max_aspect_ratio.max(1.0));
+            u16::try_from(side).unwrap_or(AspectRatio::SCALE)
+        } else {
+            0
+        };
+        if self.min_aspect_side != min_aspect_side {
+            self.min_aspect_side = min_aspect_side;
+            self.invalidate(0);
+        }
     }
 
-    pub fn set_padding(&mut self, padding: u16) {
-        self.padding = padding;
+    pub fn set_direction(&mut self, direction: Direction) {
+        if self.direction != direction {
+            self.direction = direction;
+            self.invalidate(0);
+        }
     }
 
     pub fn resize(&mut self, new_len: usize) {
+        if new_len != self.num_items {
+            self.invalidate(new_len.min(self.num_items));
+        }
+        if let Some(removed) = self.spans.get(new_len..self.num_items) {
+            if removed.iter().any(|span| *span != Span::default()) {
+                self.invalidate(self.group_start(new_len.saturating_sub(1)));
+            }
+        }
+        if let Some(removed) = self.positions.get(new_len..) {
+            if removed.iter().any(Option::is_some) {
+                self.invalidate(self.group_start(new_len.saturating_sub(1)));
+            }
+            self.positions.truncate(new_len);
+        }
+        // Spans are rarely set for every item, so new items must not keep the ones of removed items.
+        let len = self.spans.len();
+        if let Some(spans) = self
+            .spans
+            .get_mut(self.num_items.min(len)..new_len.min(len))
+        {
+            spans.fill(Span::default());
+        }
         self.num_items = new_len;
         let len = self.transforms.len().min(self.aspect_ratios.len());
         if new_len > len {
             self.transforms.resize_with(new_len, Default::default);
             self.aspect_ratios.resize_with(new_len, Default::default);
+            self.spans.resize_with(new_len, Default::default);
+        }
+    }
+
+    /// Starts a new group at the item index.
+    ///
+    /// Groups are expected to be added in ascending order. A start index smaller than the one of
+    /// the previous group is moved to the previous start, which results in an empty group.
+    pub fn add_group(&mut self, start: usize, header_height: u16) {
+        let start = self
+            .groups
+            .last()
+            .map_or(start, |group| start.max(group.start));
+        // Splitting a group changes how the items in front are laid out too, for example if only
+        // the new group has spans or the last row of the group in front changes.
+        self.invalidate(self.group_start(start));
+        self.groups.push(Group {
+            start,
+            header_height,
+        });
+        self.header_transforms.push(Transform::default());
+    }
+
+    pub fn clear_groups(&mut self) {
+        if !self.groups.is_empty() {
+            self.groups.clear();
+            self.header_transforms.clear();
+            self.invalidate(0);
         }
     }
 
@@ -75,117 +736,855 @@ impl Layout {
     // Could also have an approximated version for very large lists, and just properly compute for what in and close to the viewport
     // TODO: Look up proper masonry algorithm, e.g. https://euler.stephan-brumme.com/215/
     // TODO: Alternatively, could layout based on aspect ratio blogpost https://medium.com/@danrschlosser/building-the-image-grid-from-google-photos-6a09e193c74a
-    pub fn compute_horizontal(&mut self, container_width: u16) -> u32 {
+    pub fn compute_horizontal(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::Horizontal,
+            container_width,
+            Layout::layout_horizontal,
+            is_cancelled,
+        )
+    }
+
+    // Main idea: Like the horizontal layout but instead of breaking a row as soon as it is full, all
+    // possible rows are considered and the ones that deviate the least from the thumbnail size are
+    // chosen (like the Knuth-Plass line breaking algorithm for text)
+    pub fn compute_justified(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::Justified,
+            container_width,
+            Layout::layout_justified,
+            is_cancelled,
+        )
+    }
+
+    // Main idea: Initialize with N columns of identical widths
+    // loop over images, put them in the column that has the least height filled
+    pub fn compute_vertical(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::Vertical,
+            container_width,
+            Layout::layout_vertical,
+            is_cancelled,
+        )
+    }
+
+    pub fn compute_vertical_ordered(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::VerticalOrdered,
+            container_width,
+            |layout, items, dirty, container_width, top, is_cancelled| {
+                let placement = Placement::Ordered;
+                layout.layout_masonry(placement, items, dirty, container_width, top, is_cancelled)
+            },
+            is_cancelled,
+        )
+    }
+
+    pub fn compute_vertical_balanced(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::VerticalBalanced,
+            container_width,
+            |layout, items, dirty, container_width, top, is_cancelled| {
+                let placement = Placement::Balanced;
+                layout.layout_masonry(placement, items, dirty, container_width, top, is_cancelled)
+            },
+            is_cancelled,
+        )
+    }
+
+    // Main idea: Items with a supplied position stay where they are, all other items are placed
+    // at the lowest free spot in between, like a moodboard
+    pub fn compute_freeform(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::Freeform,
+            container_width,
+            Layout::layout_freeform,
+            is_cancelled,
+        )
+    }
+
+    // Simple Grid layout, replacement for the react-window dependency
+    pub fn compute_grid(
+        &mut self,
+        container_width: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        self.compute_groups(
+            MasonryType::Grid,
+            container_width,
+            Layout::layout_grid,
+            is_cancelled,
+        )
+    }
+}
+
+impl Layout {
+    fn is_empty(&self) -> bool {
+        self.num_items == 0
+    }
+
+    /// Returns the first item of the group containing the item.
+    fn group_start(&self, index: usize) -> usize {
+        self.groups
+            .iter()
+            .map(|group| group.start)
+            .take_while(|&start| start <= index)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Marks the transforms of all items from the index onwards as out of date.
+    fn invalidate(&mut self, index: usize) {
+        self.dirty = Some(self.dirty.map_or(index, |dirty| dirty.min(index)));
+    }
+
+    /// Lays out all groups below each other and returns the height of the container.
+    ///
+    /// Every group is computed independently with the passed function, which receives the items of
+    /// the group, the first out of date item, the container width without the inset and the top
+    /// offset where the group starts. It returns the top offset below the group including the row
+    /// gap, which is removed again after the last group. Groups in front of the first out of date
+    /// item are skipped entirely. The group containing the item before it is computed again from
+    /// the last state that is not affected.
+    ///
+    /// The computation is stopped between rows if `is_cancelled` returns true, in which case
+    /// `None` is returned and the items stay out of date.
+    fn compute_groups(
+        &mut self,
+        kind: MasonryType,
+        container_width: u32,
+        layout_group: LayoutGroup,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
         if self.is_empty() || self.thumbnail_size == 0 {
-            return 0;
+            self.clear_computation();
+            return Some(0);
+        }
+        if self.computed != Some((kind, container_width)) {
+            self.computed = Some((kind, container_width));
+            self.invalidate(0);
         }
+        let num_items = self.num_items;
+        // The transforms of a cancelled computation were never read, so the ones from before it
+        // are kept.
+        if let (true, Some(len)) = (self.retain_previous, self.laid_out.take()) {
+            self.previous_transforms.clear();
+            self.previous_transforms
+                .extend_from_slice(&self.transforms[..len.min(self.transforms.len())]);
+        }
+        let dirty = match self.dirty.take() {
+            Some(dirty) => dirty.min(num_items),
+            None => {
+                self.laid_out = Some(num_items);
+                self.resolve_anchor();
+                return Some(self.height);
+            }
+        };
+        // All layouts are computed from left to right without the inset and converted afterwards.
+        if let Some(mapping) = self.mapped.take() {
+            self.map_direction(mapping, true);
+        }
+        let inset = u32::from(self.inset);
+        let container_width = container_width.saturating_sub(2 * inset);
+        let mut top = 0;
+        let mut trailing_gap = 0;
 
-        let container_width = container_width.max(self.thumbnail_size);
-        let height = u32::from(self.thumbnail_size);
-        let max_width = u32::from(container_width);
-        let container_width = f32::from(container_width);
-        let padding = u32::from(self.padding);
+        // The first group contains the items in front of the first header.
+        for group in 0..=self.groups.len() {
+            let start = match group.checked_sub(1) {
+                Some(header) => self.groups[header].start.min(num_items),
+                None => 0,
+            };
+            let end = self
+                .groups
+                .get(group)
+                .map_or(num_items, |next| next.start.min(num_items));
 
-        let mut top = 0;
-        let mut row_width = 0;
-        let mut start = 0;
+            // The group does not contain any out of date item, so it stays as it is.
+            if end < dirty && group < self.groups.len() {
+                top = self.header_transforms[group].0.get::<2>();
+                continue;
+            }
 
-        for end in 0..self.num_items {
-            // Correct aspect ratio for very wide/narrow images
-            let width = self.aspect_ratios[end].correct_width(height);
+            if let Some(header) = group.checked_sub(1) {
+                let header_height = u32::from(self.groups[header].header_height);
+                // width | height | top | left
+                self.header_transforms[header].0 = U32x4::new(
+                    container_width.max(self.thumbnail_size),
+                    header_height,
+                    top,
+                    0,
+                );
+                top += header_height;
+                trailing_gap = 0;
+            }
 
-            self.transforms[end].0 = U32x4::new(width, height, top, row_width);
+            if start < end {
+                let items = start..end;
+                match layout_group(
+                    self,
+                    items,
+                    dirty.max(start),
+                    container_width,
+                    top,
+                    is_cancelled,
+                ) {
+                    Some(bottom) => {
+                        top = bottom;
+                        trailing_gap = u32::from(self.row_gap);
+                    }
+                    None => {
+                        self.invalidate(dirty);
+                        return None;
+                    }
+                }
+            }
+        }
 
-            row_width += width + padding;
+        self.finish(container_width, top.saturating_sub(trailing_gap));
+        self.laid_out = Some(num_items);
+        self.resolve_anchor();
+        Some(self.height)
+    }
 
-            // Check if adding this image to the row would exceed the container width
-            if row_width > max_width {
-                // If it exceeds it, scale all current items in the row accordingly and start a new row.
-                // width | height | top | left
-                let factor = container_width / f32::from(row_width as u16);
-                let factor = F32x4::from(factor).set::<2>(1.0); // Do not scale top
-                for transform in self.transforms.get_mut(start..=end).unwrap_or_abort() {
-                    transform.0 = U32x4::from(F32x4::from(transform.0) * factor);
+    /// Removes everything that was found by the most recent computation, so that queries do not
+    /// return items which no longer exist, and lays out all items again in the next computation.
+    fn clear_computation(&mut self) {
+        self.index.build(&[], 0, 1);
+        self.header_transforms.fill(Transform::default());
+        self.height = 0;
+        self.computed = None;
+        self.mapped = None;
+        self.laid_out = None;
+        self.anchor = None;
+        self.scroll_position = None;
+        self.invalidate(0);
+    }
+
+    /// Builds the spatial index from the left to right transforms without the inset and converts
+    /// them to the direction with the inset.
+    fn finish(&mut self, container_width: u32, height: u32) {
+        let transforms = self.transforms.get(..self.num_items).unwrap_or_abort();
+        self.index.build(transforms, height, self.thumbnail_size);
+        self.height = height.saturating_add(2 * u32::from(self.inset));
+
+        if let Some(mapping) = self.mapping(container_width) {
+            self.map_direction(mapping, false);
+            self.mapped = Some(mapping);
+        }
+    }
+
+    /// Returns how the transforms are converted for the container width without the inset or
+    /// `None` if they stay as they are.
+    fn mapping(&self, container_width: u32) -> Option<Mapping> {
+        let inset = u32::from(self.inset);
+        (self.direction != Direction::LeftToRight || inset > 0).then(|| Mapping {
+            direction: self.direction,
+            container_width: container_width.max(self.thumbnail_size) + 2 * inset,
+            inset,
+            num_items: self.num_items,
+            num_headers: self.header_transforms.len(),
+        })
+    }
+
+    /// Returns the start of every snapshot: the version, the kind, the container width and all
+    /// settings that change the layout.
+    fn snapshot_key(&self, kind: MasonryType, container_width: u32) -> Vec<u8> {
+        let mut key = Writer::default();
+        key.bytes(&snapshot::MAGIC);
+        key.u8(snapshot::VERSION);
+        key.u8(kind as u8);
+        key.u32(container_width);
+        key.u32(self.thumbnail_size);
+        key.u16(self.column_gap);
+        key.u16(self.row_gap);
+        key.u16(self.inset);
+        key.u16(self.snap);
+        key.u16(self.caption_height);
+        let (column_mode, first, second) = self.column_mode;
+        key.u8(column_mode as u8);
+        key.u32(first);
+        key.u32(second);
+        key.u8(self.max_row_deviation);
+        key.u8(self.last_row as u8);
+        key.u8(self.direction as u8);
+        key.u16(self.min_aspect_side);
+        key.0
+    }
+
+    /// Computes the scroll position from the anchor, which is only used once.
+    fn resolve_anchor(&mut self) {
+        self.scroll_position = self.anchor.take().and_then(|(index, offset)| {
+            let transform = &self.transforms.get(..self.num_items)?.get(index)?.0;
+            let top = match self.direction {
+                Direction::TopToBottom => transform.get::<3>(),
+                _ => transform.get::<2>(),
+            };
+            Some(u32::try_from((i64::from(top) - i64::from(offset)).max(0)).unwrap_or(u32::MAX))
+        });
+    }
+
+    /// Converts the transforms from the left to right layout without the inset to the direction
+    /// with the inset or back if `restore` is true.
+    fn map_direction(&mut self, mapping: Mapping, restore: bool) {
+        let container_width = mapping.container_width;
+        let inset = mapping.inset;
+        let num_items = mapping.num_items.min(self.num_items);
+        let num_headers = mapping.num_headers.min(self.header_transforms.len());
+        let transforms = self.transforms[..num_items]
+            .iter_mut()
+            .chain(&mut self.header_transforms[..num_headers]);
+        for transform in transforms {
+            // width | height | top | left
+            let [mut width, mut height, mut top, mut left] = transform.0.to_array();
+            if !restore {
+                (top, left) = (top.saturating_add(inset), left.saturating_add(inset));
+            }
+            // Both conversions are their own inverse.
+            match mapping.direction {
+                Direction::LeftToRight => {}
+                Direction::RightToLeft => {
+                    left = container_width.wrapping_sub(left).wrapping_sub(width);
+                }
+                Direction::TopToBottom => {
+                    (width, height, top, left) = (height, width, left, top);
+                }
+            }
+            if restore {
+                (top, left) = (top.saturating_sub(inset), left.saturating_sub(inset));
+            }
+            transform.0 = U32x4::new(width, height, top, left);
+        }
+    }
+
+    /// Returns the first item of the second row in front of the row containing the item before the
+    /// dirty one and its top offset.
+    ///
+    /// The rows of the horizontal and grid layouts only depend on the items from their first item
+    /// onwards. The row in front is included because the last row can take items from it. If the
+    /// item was in the last row, that row started where the balancing moved it, so the row in
+    /// front can become the new last row and the one in front of it is included as well.
+    fn resume_row(&self, items: &Range<usize>, dirty: usize) -> (usize, u32) {
+        let row_start = |end: usize| {
+            let top = self.transforms[end].0.get::<2>();
+            (items.start..end)
+                .rev()
+                .find(|&index| self.transforms[index].0.get::<2>() != top)
+                .map_or(items.start, |index| index + 1)
+        };
+
+        if dirty == items.start {
+            return (items.start, self.transforms[items.start].0.get::<2>());
+        }
+        let mut start = row_start(dirty - 1);
+        for _ in 0..2 {
+            if start > items.start {
+                start = row_start(start - 1);
+            }
+        }
+        (start, self.transforms[start].0.get::<2>())
+    }
+
+    fn layout_horizontal(
+        &mut self,
+        items: Range<usize>,
+        dirty: usize,
+        container_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        let container_width = container_width.max(self.thumbnail_size);
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        let (start, mut top) = if dirty > items.start {
+            self.resume_row(&items, dirty)
+        } else {
+            (items.start, top)
+        };
+
+        let mut row_width: u32 = 0;
+        let mut start = start;
+        let mut previous_row = None;
+
+        for end in start..items.end {
+            // Correct aspect ratio for very wide/narrow images
+            row_width =
+                row_width.saturating_add(self.natural_width(end).saturating_add(column_gap));
+
+            // Check if adding this image to the row would exceed the container width. The gap
+            // after the last item of a row does not count.
+            if row_width > container_width.saturating_add(column_gap) {
+                if is_cancelled() {
+                    return None;
                 }
+                // If it exceeds it, scale all current items in the row accordingly and start a new row.
+                let next = end + 1;
+                let height = self.fill_row(start..next, top, container_width);
+                previous_row = Some(start..next);
 
                 // Start a new row
                 row_width = 0;
-                start = end + 1;
-                top += self.transforms[end].0.get::<1>() + padding;
+                start = next;
+                top += height + row_gap;
             }
         }
         // Return the height of the container: If a new row was just started, no need to add last item's height; already done in the loop
-        if row_width == 0 {
-            top
+        if start == items.end {
+            Some(top)
         } else {
-            top + height + padding
+            Some(self.place_last_row(previous_row, start..items.end, top, container_width))
         }
     }
 
-    // Main idea: Initialize with N columns of identical widths
-    // loop over images, put them in the column that has the least height filled
-    pub fn compute_vertical(&mut self, container_width: u16) -> u32 {
-        use vertical_masonry::ColumnHeights;
+    fn layout_justified(
+        &mut self,
+        items: Range<usize>,
+        dirty: usize,
+        container_width: u32,
+        mut top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        let container_width = container_width.max(self.thumbnail_size);
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        let max_scale = 1.0 + f32::from(self.max_row_deviation) / 100.0;
 
-        if self.is_empty() || self.thumbnail_size == 0 {
-            return 0;
+        let widths = items
+            .clone()
+            .map(|index| self.natural_width(index))
+            .collect::<Vec<u32>>();
+        // The best rows ending before the dirty item did not change, so only the rows ending at
+        // or after it have to be found again. The positions of all rows can still change.
+        if self.row_breaks.len() <= self.num_items {
+            self.row_breaks.resize(self.num_items + 1, (0.0, 0));
         }
+        let row_ends = justified::partition(
+            &widths,
+            self.row_breaks
+                .get_mut(items.start..=items.end)
+                .unwrap_or_abort(),
+            dirty - items.start,
+            container_width,
+            column_gap,
+            max_scale - 1.0,
+            is_cancelled,
+        )?;
 
-        let (column_width, mut columns) = {
-            let container_width = container_width.max(self.thumbnail_size);
-            let n_columns = container_width.div_int(self.thumbnail_size);
-            let column_width = u32::from(container_width.div_int(n_columns));
-            (column_width, ColumnHeights::new(usize::from(n_columns)))
+        let mut start = items.start;
+        let mut previous_row = None;
+        for end in row_ends {
+            let end = items.start + end;
+            let row_width = self.row_width(start..end);
+            let scale = row_scale(row_width, end - start, container_width, column_gap);
+            if end == items.end && scale >= 1.0 {
+                return Some(self.place_last_row(previous_row, start..end, top, container_width));
+            }
+
+            let height = if scale <= max_scale {
+                self.fill_row(start..end, top, container_width)
+            } else {
+                self.place_row(start..end, top, max_scale)
+            };
+            top += height + row_gap;
+            previous_row = Some(start..end);
+            start = end;
+        }
+        Some(top)
+    }
+
+    /// Places the items of the last row, which does not fill the container width, according to
+    /// the [`LastRow`] policy and returns the height of the container.
+    fn place_last_row(
+        &mut self,
+        previous_row: Option<Range<usize>>,
+        last_row: Range<usize>,
+        top: u32,
+        container_width: u32,
+    ) -> u32 {
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        let row_width = self.row_width(last_row.clone());
+
+        let height = match (self.last_row, previous_row) {
+            (LastRow::Justify, _) => self.fill_row(last_row, top, container_width),
+            (LastRow::Center, _) => {
+                let height = self.place_row(last_row.clone(), top, 1.0);
+                // The trailing gap does not count towards the width of the row.
+                let offset = (container_width + column_gap).saturating_sub(row_width) / 2;
+                for transform in self.transforms.get_mut(last_row).unwrap_or_abort() {
+                    transform.0 += U32x4::new(0, 0, 0, offset);
+                }
+                height
+            }
+            (LastRow::Balance, Some(previous_row)) => {
+                return self.balance_last_rows(previous_row, last_row, container_width);
+            }
+            (LastRow::Keep | LastRow::Balance, _) => self.place_row(last_row, top, 1.0),
         };
-        let padding = u32::from(self.padding);
-        let item_width = column_width - padding;
+        top + height + row_gap
+    }
 
-        for (transform, aspect_ratio) in self
-            .transforms
-            .iter_mut()
-            .zip(self.aspect_ratios.iter())
-            .take(self.num_items)
-        {
-            let height = aspect_ratio.correct_height(item_width);
-            let (top, shortest_column_index) = columns.min_column();
-            let left = shortest_column_index * column_width;
+    /// Moves items from the end of the previous row to the last row as long as it makes the widths
+    /// of both rows more even and returns the height of the container.
+    ///
+    /// The previous row fills the container width but is not stretched more than the maximum row
+    /// deviation allows. The last row gets the same height if it fits.
+    fn balance_last_rows(
+        &mut self,
+        previous_row: Range<usize>,
+        last_row: Range<usize>,
+        container_width: u32,
+    ) -> u32 {
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        let max_scale = 1.0 + f32::from(self.max_row_deviation) / 100.0;
+        let mut previous_width = self.row_width(previous_row.clone());
+        let mut last_width = self.row_width(last_row.clone());
 
-            // SAFETY: ColumnHeights::min_column returns a valid column index.
-            unsafe {
-                columns.set_min_column(shortest_column_index, top + height + padding);
+        let mut split = last_row.start;
+        while split - previous_row.start > 1 {
+            let width = self.natural_width(split - 1) + column_gap;
+            let previous_len = split - 1 - previous_row.start;
+            let difference = previous_width.abs_diff(last_width);
+            if difference <= (previous_width - width).abs_diff(last_width + width)
+                || last_width + width > container_width + column_gap
+                || row_scale(
+                    previous_width - width,
+                    previous_len,
+                    container_width,
+                    column_gap,
+                ) > max_scale
+            {
+                break;
             }
+            previous_width -= width;
+            last_width += width;
+            split -= 1;
+        }
+
+        let top = self.transforms[previous_row.start].0.get::<2>();
+        let previous_row = previous_row.start..split;
+        let scale = row_scale(
+            previous_width,
+            previous_row.len(),
+            container_width,
+            column_gap,
+        );
+        let top = top + self.fill_row(previous_row, top, container_width) + row_gap;
+
+        let last_row = split..last_row.end;
+        let last_scale = row_scale(last_width, last_row.len(), container_width, column_gap);
+        top + self.place_row(last_row, top, scale.max(1.0).min(last_scale)) + row_gap
+    }
+
+    /// Places the items of a row next to each other at the thumbnail size scaled by the factor and
+    /// returns the height of the row including the caption.
+    ///
+    /// The gaps between the items are not scaled. The edges of the items are rounded instead of
+    /// their widths, so that the rounding errors do not add up along the row.
+    fn place_row(&mut self, row: Range<usize>, top: u32, scale: f32) -> u32 {
+        let height = round_down(to_f32(self.thumbnail_size) * scale)
+            .saturating_add(u32::from(self.caption_height));
+        let column_gap = u32::from(self.column_gap);
+        let round = |length: u32| round_down(to_f32(length) * scale + 0.5);
 
-            transform.0 = U32x4::new(item_width, height, top, left);
+        let mut natural_left: u32 = 0;
+        let mut gaps: u32 = 0;
+        for index in row {
+            let natural_right = natural_left.saturating_add(self.natural_width(index));
+            let (left, right) = (round(natural_left), round(natural_right));
+            // width | height | top | left
+            self.transforms[index].0 = U32x4::new(right - left, height, top, left + gaps);
+            natural_left = natural_right;
+            gaps = gaps.saturating_add(column_gap);
         }
-        columns.max_height()
+        height
     }
 
-    // Simple Grid layout, replacement for the react-window dependency
-    pub fn compute_grid(&mut self, container_width: u16) -> u32 {
-        if self.is_empty() || self.thumbnail_size == 0 {
-            return 0;
+    /// Places the items of a row so that they exactly fill the container width and returns the
+    /// height of the row.
+    fn fill_row(&mut self, row: Range<usize>, top: u32, container_width: u32) -> u32 {
+        let column_gap = u32::from(self.column_gap);
+        let scale = row_scale(
+            self.row_width(row.clone()),
+            row.len(),
+            container_width,
+            column_gap,
+        );
+        let height = self.place_row(row.clone(), top, scale);
+
+        // Rounding the scaled widths can leave the last item a pixel short of the container edge.
+        // Gaps that do not fit into the container only occur when all items are scaled to nothing.
+        let last = row.end - 1;
+        for transform in self.transforms.get_mut(row).unwrap_or_abort() {
+            let left = transform.0.get::<3>().min(container_width);
+            transform.0 = transform.0.set::<3>(left);
         }
+        let left = self.transforms[last].0.get::<3>();
+        self.transforms[last].0 = self.transforms[last].0.set::<0>(container_width - left);
+        height
+    }
+
+    /// Returns the width of the item at the thumbnail size.
+    fn natural_width(&self, index: usize) -> u32 {
+        self.aspect_ratios[index]
+            .in_layout(self.direction, self.min_aspect_side)
+            .correct_width(self.thumbnail_size)
+    }
 
+    /// Returns the width of the items including the gap after every item at the thumbnail size.
+    fn row_width(&self, row: Range<usize>) -> u32 {
+        let column_gap = u32::from(self.column_gap);
+        row.fold(0, |width, index| {
+            width.saturating_add(self.natural_width(index).saturating_add(column_gap))
+        })
+    }
+
+    /// Returns the number of columns and the distance between the left edges of two adjacent
+    /// columns for the vertical and grid layouts.
+    fn columns(&self, container_width: u32) -> (usize, u32) {
+        let container_width = container_width.max(self.thumbnail_size);
+        let column_gap = u32::from(self.column_gap);
+        let n_columns = match self.column_mode {
+            (ColumnMode::Auto, ..) => container_width.div_int(self.thumbnail_size),
+            (ColumnMode::Fixed | ColumnMode::Fit, count, _) => count,
+            (ColumnMode::Width, min_width, _) => {
+                (container_width + column_gap) / min_width.saturating_add(column_gap).max(1)
+            }
+        }
+        // Columns are at least 1 pixel wide.
+        .clamp(1, container_width);
+        // Rounding down keeps the last column inside the container. The gap after it does not
+        // count towards the container width.
+        let column_width = (container_width + column_gap) / n_columns;
+        let max_width = match self.column_mode {
+            (ColumnMode::Fixed, ..) => self.thumbnail_size,
+            (ColumnMode::Width, _, max_width) if max_width > 0 => max_width,
+            _ => u32::MAX,
+        };
+        (
+            n_columns as usize,
+            column_width.min(max_width.saturating_add(column_gap)),
+        )
+    }
+
+    fn layout_vertical(
+        &mut self,
+        items: Range<usize>,
+        dirty: usize,
+        container_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        let placement = Placement::Shortest;
+        self.layout_masonry(placement, items, dirty, container_width, top, is_cancelled)
+    }
+
+    /// Places the items in columns of equal width according to the placement strategy.
+    fn layout_masonry(
+        &mut self,
+        placement: Placement,
+        items: Range<usize>,
+        dirty: usize,
+        container_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        use vertical_masonry::ColumnHeights;
+
+        let (n_columns, column_width) = self.columns(container_width);
+        if self.has_spans(&items) {
+            return self.layout_vertical_spans(items, n_columns, column_width, top, is_cancelled);
+        }
+        let mut columns = ColumnHeights::new(n_columns, top);
+        let row_gap = u32::from(self.row_gap);
+        let item_width = column_width.saturating_sub(u32::from(self.column_gap));
+        let caption_height = u32::from(self.caption_height);
+        let (direction, min_aspect_side) = (self.direction, self.min_aspect_side);
+        // The balanced placement depends on all items of a row, so it starts again at its first.
+        let dirty = match placement {
+            Placement::Balanced => items.start + (dirty - items.start) / n_columns * n_columns,
+            Placement::Shortest | Placement::Ordered => dirty,
+        };
+
+        let mut previous_column = self.restore_columns(
+            placement,
+            &items,
+            dirty,
+            n_columns,
+            column_width,
+            &mut columns,
+        );
+
+        let items = dirty..items.end;
+        let rows = self
+            .transforms
+            .get_mut(items.clone())
+            .unwrap_or_abort()
+            .chunks_mut(n_columns)
+            .zip(
+                self.aspect_ratios
+                    .get(items)
+                    .unwrap_or_abort()
+                    .chunks(n_columns),
+            );
+        let mut heights = Vec::with_capacity(n_columns);
+        let mut order = Vec::with_capacity(n_columns);
+        for (transforms, aspect_ratios) in rows {
+            if is_cancelled() {
+                return None;
+            }
+            heights.clear();
+            heights.extend(aspect_ratios.iter().map(|aspect_ratio| {
+                aspect_ratio
+                    .in_layout(direction, min_aspect_side)
+                    .correct_height(item_width)
+                    .saturating_add(caption_height)
+            }));
+            order.clear();
+            order.extend(0..transforms.len());
+            if placement == Placement::Balanced {
+                // Longest processing time first: The highest item goes into the shortest column.
+                order.sort_by(|&a, &b| heights[b].cmp(&heights[a]));
+            }
+
+            for &offset in &order {
+                let height = heights[offset];
+                let (min_height, shortest_column) = columns.min_column();
+                let column = match placement {
+                    Placement::Ordered => {
+                        let next = (previous_column + 1) % to_u32(n_columns);
+                        if columns.get(next) <= min_height.saturating_add(item_width) {
+                            next
+                        } else {
+                            shortest_column
+                        }
+                    }
+                    Placement::Shortest | Placement::Balanced => shortest_column,
+                };
+                let top = columns.get(column);
+                let left = column * column_width;
+                previous_column = column;
+
+                // SAFETY: Both the shortest and the next column are valid column indices.
+                unsafe {
+                    columns.set_min_column(column, top + height + row_gap);
+                }
+
+                transforms[offset].0 = U32x4::new(item_width, height, top, left);
+            }
+        }
+        Some(columns.max_height())
+    }
+
+    /// Restores the column heights from the last item of every column in front of the dirty item
+    /// and returns the column of the item before it.
+    ///
+    /// The balanced placement does not place the items of a row in order, so all items of that row
+    /// in the column are taken into account.
+    fn restore_columns(
+        &self,
+        placement: Placement,
+        items: &Range<usize>,
+        dirty: usize,
+        n_columns: usize,
+        column_width: u32,
+        columns: &mut vertical_masonry::ColumnHeights,
+    ) -> u32 {
+        let row_gap = u32::from(self.row_gap);
+        let last_column = to_u32(n_columns) - 1;
+        let column_of = |transform: &U32x4| (transform.get::<3>() / column_width).min(last_column);
+        let row_of = |index: usize| match placement {
+            Placement::Balanced => (index - items.start) / n_columns,
+            Placement::Shortest | Placement::Ordered => index,
+        };
+        let mut found_in_row = vec![None; n_columns];
+        let mut remaining = n_columns;
+        let mut last_row = None;
+        for index in (items.start..dirty).rev() {
+            let row = row_of(index);
+            if last_row.map_or(false, |last_row| row < last_row) {
+                break;
+            }
+            let transform = &self.transforms[index].0;
+            let column = column_of(transform);
+            let first = found_in_row[column as usize].is_none();
+            if *found_in_row[column as usize].get_or_insert(row) == row {
+                let bottom = transform.get::<2>() + transform.get::<1>() + row_gap;
+                if first {
+                    remaining -= 1;
+                    if remaining == 0 {
+                        last_row = Some(row);
+                    }
+                }
+                // SAFETY: The column index is clamped to the number of columns.
+                unsafe {
+                    columns.set_min_column(column, bottom.max(columns.get(column)));
+                }
+            }
+        }
+        match dirty.checked_sub(1) {
+            Some(previous) if previous >= items.start => column_of(&self.transforms[previous].0),
+            _ => last_column,
+        }
+    }
+
+    fn layout_grid(
+        &mut self,
+        items: Range<usize>,
+        dirty: usize,
+        container_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
         // Main idea: Put items in a grid.
-        let (n_columns, row_height) = {
-            let container_width = container_width.max(self.thumbnail_size);
-            let n_columns = container_width.div_int(self.thumbnail_size);
-            let column_width = u32::from(container_width.div_int(n_columns));
-            (usize::from(n_columns), column_width)
+        let (n_columns, column_width) = self.columns(container_width);
+        if self.has_spans(&items) {
+            return self.layout_grid_spans(items, n_columns, column_width, top, is_cancelled);
+        }
+        let item_size = column_width.saturating_sub(u32::from(self.column_gap));
+        let item_height = item_size + u32::from(self.caption_height);
+        let row_height = item_height + u32::from(self.row_gap);
+        let (start, top) = if dirty > items.start {
+            self.resume_row(&items, dirty)
+        } else {
+            (items.start, top)
         };
-        let item_size = row_height - u32::from(self.padding);
 
         let rows = self
             .transforms
-            .get_mut(..self.num_items)
+            .get_mut(start..items.end)
             .unwrap_or_abort()
             .chunks_mut(n_columns);
 
         // width | height | top | left
-        let mut item_transform = U32x4::new(item_size, item_size, 0, 0);
+        let mut item_transform = U32x4::new(item_size, item_height, top, 0);
         let increment_top = U32x4::new(0, 0, row_height, 0);
-        let increment_left = U32x4::new(0, 0, 0, row_height);
+        let increment_left = U32x4::new(0, 0, 0, column_width);
         for row in rows {
+            if is_cancelled() {
+                return None;
+            }
             for transform in row.iter_mut() {
                 transform.0 = item_transform;
                 item_transform += increment_left;
@@ -194,68 +1593,251 @@ impl Layout {
             item_transform = item_transform.set::<3>(0); // Reset left offset
         }
         // Return total height of the grid
-        item_transform.get::<2>()
+        Some(item_transform.get::<2>())
     }
 }
 
 impl Layout {
-    fn is_empty(&self) -> bool {
-        self.num_items == 0
+    /// Returns true if any item covers more than one cell.
+    ///
+    /// Items with spans can be moved into holes in front of them, so groups containing them are
+    /// always computed again from their first item.
+    fn has_spans(&self, items: &Range<usize>) -> bool {
+        self.spans[items.clone()]
+            .iter()
+            .any(|span| *span != Span::default())
+    }
+
+    /// Places the items in the first free cells of the grid that can hold their span. Smaller
+    /// items after an item that did not fit into a row fill the holes it left.
+    fn layout_grid_spans(
+        &mut self,
+        items: Range<usize>,
+        n_columns: usize,
+        column_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        // Images are square, so the rows are as high as the columns are wide without the gaps plus
+        // the caption.
+        let row_height =
+            column_width.saturating_sub(column_gap) + u32::from(self.caption_height) + row_gap;
+        let mut grid = spans::Grid::new(n_columns);
+        for (count, index) in items.enumerate() {
+            if count % n_columns == 0 && is_cancelled() {
+                return None;
+            }
+            let span = self.spans[index];
+            let columns = usize::from(span.columns).min(n_columns);
+            let rows = usize::from(span.rows);
+            let (row, column) = grid.place(columns, rows);
+            // width | height | top | left
+            self.transforms[index].0 = U32x4::new(
+                (to_u32(columns) * column_width).saturating_sub(column_gap),
+                (u32::from(span.rows) * row_height).saturating_sub(row_gap),
+                top + to_u32(row) * row_height,
+                to_u32(column) * column_width,
+            );
+        }
+        Some(top + to_u32(grid.rows()) * row_height)
+    }
+
+    /// Places every item in the adjacent columns that fit its span and end the highest. Items that
+    /// fit into a hole below an item spanning multiple columns are placed there instead.
+    fn layout_vertical_spans(
+        &mut self,
+        items: Range<usize>,
+        n_columns: usize,
+        column_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        let (direction, min_aspect_side) = (self.direction, self.min_aspect_side);
+        let mut columns = spans::Columns::new(n_columns, top);
+        for (count, index) in items.enumerate() {
+            if count % n_columns == 0 && is_cancelled() {
+                return None;
+            }
+            let span = usize::from(self.spans[index].columns).min(n_columns);
+            let width = (to_u32(span) * column_width).saturating_sub(column_gap);
+            let height = self.aspect_ratios[index]
+                .in_layout(direction, min_aspect_side)
+                .correct_height(width)
+                .saturating_add(u32::from(self.caption_height));
+            let (item_top, column) = columns.place(span, height + row_gap);
+            self.transforms[index].0 =
+                U32x4::new(width, height, item_top, to_u32(column) * column_width);
+        }
+        Some(columns.max_height())
+    }
+
+    /// Places the items with a supplied position there and all others at the lowest spot in
+    /// between where they fit at the thumbnail size.
+    ///
+    /// Supplied positions are rounded to the snap size and moved into the container. They can
+    /// overlap each other, but the other items never overlap any item. The group always ends
+    /// below its lowest item.
+    fn layout_freeform(
+        &mut self,
+        items: Range<usize>,
+        _dirty: usize,
+        container_width: u32,
+        top: u32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<u32> {
+        let container_width = container_width.max(self.thumbnail_size);
+        let (column_gap, row_gap) = (u32::from(self.column_gap), u32::from(self.row_gap));
+        let snap = |offset: u32| match u32::from(self.snap) {
+            0 => offset,
+            snap => (offset.saturating_add(snap / 2) / snap).saturating_mul(snap),
+        };
+        let mut skyline = freeform::Skyline::new(container_width);
+        let mut bottom = top;
+
+        // The supplied positions are placed first, so that the other items can avoid all of them.
+        for index in items.clone() {
+            if let Some(Some(position)) = self.positions.get(index).copied() {
+                let width = position.width.min(container_width);
+                let height = position.height;
+                let left = snap(position.left).min(container_width - width);
+                let item_top = snap(position.top);
+                let item_bottom = item_top.saturating_add(height).saturating_add(row_gap);
+                skyline.raise(left, width.saturating_add(column_gap), item_bottom);
+                bottom = bottom.max(top.saturating_add(item_bottom));
+                let item_top = top.saturating_add(item_top);
+                self.transforms[index].0 = U32x4::new(width, height, item_top, left);
+            }
+        }
+        let (direction, min_aspect_side) = (self.direction, self.min_aspect_side);
+        for index in items {
+            if let Some(Some(_)) = self.positions.get(index) {
+                continue;
+            }
+            if is_cancelled() {
+                return None;
+            }
+            // The longer side of the image is as long as the thumbnail size.
+            let aspect_ratio = self.aspect_ratios[index].in_layout(direction, min_aspect_side);
+            let width = scale(self.thumbnail_size, aspect_ratio.width, AspectRatio::SCALE);
+            let height = scale(self.thumbnail_size, aspect_ratio.height, AspectRatio::SCALE)
+                .saturating_add(u32::from(self.caption_height));
+            let (left, item_top) = skyline.place(width, column_gap, height.saturating_add(row_gap));
+            let item_top = top.saturating_add(item_top);
+            bottom = bottom.max(item_top.saturating_add(height).saturating_add(row_gap));
+            self.transforms[index].0 = U32x4::new(width, height, item_top, left);
+        }
+        Some(bottom)
     }
 }
 
 impl AspectRatio {
+    /// Length of the longer side of every aspect ratio.
+    const SCALE: u16 = 10_000;
+
     fn set(&mut self, src_width: u16, src_height: u16) {
         let (width, height) = correct_aspect_ratio(src_width, src_height);
         self.width = width;
         self.height = height;
     }
 
-    fn width(&self) -> u32 {
-        u32::from(self.width)
-    }
-
-    fn height(&self) -> u32 {
-        u32::from(self.height)
+    /// Returns the aspect ratio as it is used by the layouts.
+    ///
+    /// The shorter side is at least `min_side` long and the aspect ratio is transposed for
+    /// [`Direction::TopToBottom`], so that it is in the coordinates of the left to right layout.
+    fn in_layout(&self, direction: Direction, min_side: u16) -> AspectRatio {
+        let width = self.width.max(min_side);
+        let height = self.height.max(min_side);
+        if direction == Direction::TopToBottom {
+            AspectRatio {
+                width: height,
+                height: width,
+            }
+        } else {
+            AspectRatio { width, height }
+        }
     }
 
     fn correct_width(&self, height: u32) -> u32 {
-        (height * self.width()).div_int(self.height())
+        scale(height, self.width, self.height)
     }
 
     fn correct_height(&self, width: u32) -> u32 {
-        (width * self.height()).div_int(self.width())
+        scale(width, self.height, self.width)
     }
 }
 
-// For images with extreme aspect ratios (very narrow or wide), crop them a little
-// so that they are at most X times as wide/long as they are long/wide
-// Returns a correct height value of the image
-fn correct_aspect_ratio(w: u16, h: u16) -> (u8, u8) {
-    const MIN_ASPECT_RATIO: u32 = 100 / 3; // X times as wide as narrow or vice versa
-
-    if w > h {
-        let height = (100 * u32::from(h))
-            .div_int(u32::from(w))
-            .max(MIN_ASPECT_RATIO) as u8;
-        (100, height)
-    } else if h > w {
-        let width = (100 * u32::from(w))
-            .div_int(u32::from(h))
-            .max(MIN_ASPECT_RATIO) as u8;
-        (width, 100)
-    } else {
-        (1, 1)
+/// Converts an index or a number of items or columns, which always fits into a `u32` on
+/// `wasm32`.
+fn to_u32(value: usize) -> u32 {
+    u32::try_from(value).unwrap_or_abort()
+}
+
+/// Converts a length to a float, which is exact up to 2^24.
+#[allow(clippy::cast_precision_loss)]
+fn to_f32(length: u32) -> f32 {
+    length as f32
+}
+
+/// Rounds a number down to an integer, saturating at 0 and `u32::MAX` like every float cast.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn round_down(value: f32) -> u32 {
+    value as u32
+}
+
+/// Returns `length * numerator / denominator` rounded to the nearest integer.
+fn scale(length: u32, numerator: u16, denominator: u16) -> u32 {
+    let scaled = (u64::from(length) * u64::from(numerator)).div_int(u64::from(denominator));
+    u32::try_from(scaled).unwrap_or(u32::MAX)
+}
+
+/// Returns the factor by which the items of a row have to be scaled to fill the container width.
+///
+/// The row width includes the gap after every item. Only the gaps between the items take up
+/// space in the container and they are not scaled.
+fn row_scale(row_width: u32, len: usize, container_width: u32, gap: u32) -> f32 {
+    let gaps = gap.saturating_mul(to_u32(len));
+    let available = container_width.saturating_add(gap).saturating_sub(gaps);
+    to_f32(available) / to_f32(row_width.saturating_sub(gaps).max(1))
+}
+
+/// Returns the aspect ratio of an image with the longer side scaled to [`AspectRatio::SCALE`].
+///
+/// The shorter side is at least 1, even for images without a width or height. It is rounded with
+/// the same float operations as [`correct_aspect_ratios`], so both always agree.
+fn correct_aspect_ratio(w: u16, h: u16) -> (u16, u16) {
+    let scale = f32::from(AspectRatio::SCALE);
+    let short = f32::from(w.min(h)) / f32::from(w.max(h)) * scale + 0.5;
+    let short = u16::try_from(round_down(short))
+        .unwrap_or(AspectRatio::SCALE)
+        .max(1);
+    match w.cmp(&h) {
+        Ordering::Less => (short, AspectRatio::SCALE),
+        Ordering::Greater => (AspectRatio::SCALE, short),
+        Ordering::Equal => (AspectRatio::SCALE, AspectRatio::SCALE),
     }
 }
 
+/// Same as [`correct_aspect_ratio`] for four images at once.
+fn correct_aspect_ratios(w: U32x4, h: U32x4) -> (U32x4, U32x4) {
+    let scale = F32x4::from(f32::from(AspectRatio::SCALE));
+    let short = F32x4::from(w.min(h)) / F32x4::from(w.max(h)) * scale + F32x4::from(0.5);
+    let short = U32x4::from(short).max(U32x4::from(1));
+
+    let long = U32x4::from(u32::from(AspectRatio::SCALE));
+    let width = short.blend(long, w.less_than(h));
+    let height = short.blend(long, h.less_than(w));
+    (width, height)
+}
+
 trait DivInt<Rhs = Self> {
     type Output;
 
     fn div_int(self, rhs: Rhs) -> Self::Output;
 }
 
-impl DivInt for u16 {
+impl DivInt for u64 {
     type Output = Self;
 
     #[inline]
@@ -273,6 +1855,77 @@ impl DivInt for u32 {
     }
 }
 
+/// Line breaking with minimum badness, adapted for rows of images.
+///
+/// <https://en.wikipedia.org/wiki/Line_wrap_and_word_wrap#Minimum_raggedness>
+mod justified {
+    use alloc::vec::Vec;
+
+    use super::row_scale;
+
+    /// Splits the items into rows and returns the end index of every row.
+    ///
+    /// The badness of a row is the squared difference between its scale factor and 1, which is
+    /// minimized over all rows. A row may only deviate by `max_deviation` from the target height
+    /// unless it contains a single item. The last row has no badness as long as it is not wider
+    /// than the container.
+    ///
+    /// The cost and start of the best row ending before every item is stored in `breaks`, which
+    /// must be one element longer than `widths`. Entries before `first_end` are reused from the
+    /// previous call. Returns `None` if the computation was cancelled.
+    pub fn partition(
+        widths: &[u32],
+        breaks: &mut [(f32, usize)],
+        first_end: usize,
+        container_width: u32,
+        gap: u32,
+        max_deviation: f32,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> Option<Vec<usize>> {
+        let len = widths.len();
+        breaks[0] = (0.0, 0);
+
+        for end in first_end.max(1)..=len {
+            if is_cancelled() {
+                return None;
+            }
+            breaks[end] = (f32::INFINITY, 0);
+            let mut row_width: u32 = 0;
+            for start in (0..end).rev() {
+                row_width = row_width.saturating_add(widths[start].saturating_add(gap));
+                let count = end - start;
+                let deviation = row_scale(row_width, count, container_width, gap) - 1.0;
+
+                // Adding more items to the row only shrinks it further.
+                if deviation < -max_deviation && count > 1 {
+                    break;
+                }
+                let badness = if end == len && deviation >= 0.0 {
+                    0.0
+                } else if count == 1 || deviation.abs() <= max_deviation {
+                    deviation * deviation
+                } else {
+                    continue;
+                };
+
+                let cost = breaks[start].0 + badness;
+                if cost < breaks[end].0 {
+                    breaks[end] = (cost, start);
+                }
+            }
+        }
+
+        let mut row_ends = Vec::new();
+        let mut end = len;
+        while end > 0 {
+            row_ends.push(end);
+            end = breaks[end].1;
+        }
+        row_ends.reverse();
+        Some(row_ends)
+    }
+}
+
 /// http://0x80.pl/notesen/2018-10-03-simd-index-of-min.html
 mod vertical_masonry {
     use alloc::{boxed::Box, vec};
@@ -290,7 +1943,8 @@ mod vertical_masonry {
     }
 
     impl ColumnHeights {
-        pub fn new(columns: usize) -> Self {
+        /// Creates columns which all start at the top offset.
+        pub fn new(columns: usize, top: u32) -> Self {
             // If the number of columns cannot be divided by 4, it is padded with u32::MAX.
             // This way it won't effect the search in Self::min_index().
             let rest = columns % 4;
@@ -304,8 +1958,9 @@ mod vertical_masonry {
             };
             Self {
                 heights: {
-                    let mut heights = vec![U32x4::ZERO; len].into_boxed_slice();
-                    *heights.last_mut().unwrap_or_abort() = padding_mask;
+                    let mut heights = vec![U32x4::from(top); len].into_boxed_slice();
+                    let last = heights.last_mut().unwrap_or_abort();
+                    *last = last.max(padding_mask);
                     heights
                 },
                 padding_mask,
@@ -341,6 +1996,11 @@ mod vertical_masonry {
                 .unwrap_or_abort()
         }
 
+        /// Returns the height of the column, which must be smaller than the number of columns.
+        pub fn get(&self, index: u32) -> u32 {
+            self.heights[index as usize / 4].to_array()[index as usize % 4]
+        }
+
         /// # Safety
         ///
         /// The index must smaller than the total number of columns, otherwise this will result in undefined behaviour.
@@ -371,3 +2031,446 @@ mod vertical_masonry {
         }
     }
 }
+
+impl Snapshot {
+    fn read_items(reader: &mut Reader) -> Option<Vec<(AspectRatio, Span, Transform)>> {
+        let num_items = reader.u32()? as usize;
+        let mut items = Vec::with_capacity(num_items.min(reader.0.len()));
+        for _ in 0..num_items {
+            let aspect_ratio = AspectRatio {
+                width: reader.u16()?,
+                height: reader.u16()?,
+            };
+            let span = Span {
+                columns: reader.u8()?,
+                rows: reader.u8()?,
+            };
+            // The layouts divide by the sides and spans, which are never 0 or larger than the scale.
+            let (short, long) = (
+                aspect_ratio.width.min(aspect_ratio.height),
+                aspect_ratio.width.max(aspect_ratio.height),
+            );
+            if short == 0 || long != AspectRatio::SCALE || span.columns == 0 || span.rows == 0 {
+                return None;
+            }
+            items.push((aspect_ratio, span, reader.transform()?));
+        }
+        Some(items)
+    }
+
+    fn read_groups(reader: &mut Reader, num_items: usize) -> Option<(Vec<Group>, Vec<Transform>)> {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut header_transforms = Vec::new();
+        for _ in 0..reader.u32()? {
+            let group = Group {
+                start: reader.u32()? as usize,
+                header_height: reader.u16()?,
+            };
+            let previous = groups.last().map_or(0, |previous| previous.start);
+            if group.start < previous || group.start > num_items {
+                return None;
+            }
+            groups.push(group);
+            header_transforms.push(reader.transform()?);
+        }
+        Some((groups, header_transforms))
+    }
+
+    fn read_positions(reader: &mut Reader, num_items: usize) -> Option<Vec<Option<Position>>> {
+        let num_positions = reader.u32()? as usize;
+        if num_positions > num_items {
+            return None;
+        }
+        let mut positions = Vec::new();
+        for _ in 0..num_positions {
+            positions.push(match reader.u8()? {
+                0 => None,
+                _ => Some(Position {
+                    width: reader.u32()?,
+                    height: reader.u32()?,
+                    top: reader.u32()?,
+                    left: reader.u32()?,
+                }),
+            });
+        }
+        Some(positions)
+    }
+
+    fn read_row_breaks(reader: &mut Reader, num_items: usize) -> Option<Vec<(f32, usize)>> {
+        let num_row_breaks = reader.u32()? as usize;
+        if num_row_breaks > num_items + 1 {
+            return None;
+        }
+        let mut row_breaks = Vec::new();
+        for _ in 0..num_row_breaks {
+            row_breaks.push((f32::from_bits(reader.u32()?), reader.u32()? as usize));
+        }
+        Some(row_breaks)
+    }
+
+    /// Returns whether the best row ending at every item starts in front of it in the same group,
+    /// so that the rows can be followed back to the start of the group.
+    fn rows_stay_in_groups(&self) -> bool {
+        let mut bounds = self
+            .groups
+            .iter()
+            .map(|group| group.start)
+            .collect::<Vec<_>>();
+        bounds.insert(0, 0);
+        bounds.push(self.items.len());
+        let invalid_row = |bounds: &[usize]| {
+            (bounds[0] + 1..=bounds[1]).any(|end| {
+                self.row_breaks
+                    .get(end)
+                    .map_or(false, |&(_, start)| start >= end - bounds[0])
+            })
+        };
+        !bounds.windows(2).any(invalid_row)
+    }
+}
+
+/// Binary format of [`Layout::snapshot()`], in which all numbers are little endian.
+mod snapshot {
+    use alloc::vec::Vec;
+
+    use super::Transform;
+    use crate::packed::U32x4;
+
+    pub const MAGIC: [u8; 4] = *b"MSNY";
+    /// Changes whenever the format or the layouts change, so that old snapshots are not restored.
+    pub const VERSION: u8 = 1;
+
+    #[derive(Default)]
+    pub struct Writer(pub Vec<u8>);
+
+    impl Writer {
+        pub fn bytes(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+
+        pub fn u8(&mut self, value: u8) {
+            self.0.push(value);
+        }
+
+        pub fn u16(&mut self, value: u16) {
+            self.bytes(&value.to_le_bytes());
+        }
+
+        pub fn u32(&mut self, value: u32) {
+            self.bytes(&value.to_le_bytes());
+        }
+
+        pub fn transform(&mut self, transform: &Transform) {
+            for value in transform.0.to_array() {
+                self.u32(value);
+            }
+        }
+    }
+
+    /// Reads the values from the front of the bytes, which returns `None` if there are not
+    /// enough bytes left.
+    pub struct Reader<'a>(pub &'a [u8]);
+
+    impl Reader<'_> {
+        fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+            let bytes = self.0.get(..N)?.try_into().ok()?;
+            self.0 = &self.0[N..];
+            Some(bytes)
+        }
+
+        pub fn u8(&mut self) -> Option<u8> {
+            self.take().map(u8::from_le_bytes)
+        }
+
+        pub fn u16(&mut self) -> Option<u16> {
+            self.take().map(u16::from_le_bytes)
+        }
+
+        pub fn u32(&mut self) -> Option<u32> {
+            self.take().map(u32::from_le_bytes)
+        }
+
+        pub fn transform(&mut self) -> Option<Transform> {
+            let [width, height, top, left] = [self.u32()?, self.u32()?, self.u32()?, self.u32()?];
+            Some(Transform(U32x4::new(width, height, top, left)))
+        }
+    }
+}
+
+/// Packing of items at the lowest free spot of the freeform layout.
+mod freeform {
+    use alloc::{vec, vec::Vec};
+
+    /// Bottom edge of the occupied space of a container that grows downwards.
+    pub struct Skyline {
+        width: u32,
+        /// Left offset and height of every segment, which ends where the next one starts.
+        segments: Vec<(u32, u32)>,
+    }
+
+    impl Skyline {
+        pub fn new(width: u32) -> Self {
+            Skyline {
+                width,
+                segments: vec![(0, 0)],
+            }
+        }
+
+        /// Raises the skyline between left and left + width to at least the height.
+        pub fn raise(&mut self, left: u32, width: u32, height: u32) {
+            let right = left.saturating_add(width).min(self.width);
+            if left >= right {
+                return;
+            }
+            self.split(left);
+            self.split(right);
+            for segment in &mut self.segments {
+                if segment.0 >= left && segment.0 < right {
+                    segment.1 = segment.1.max(height);
+                }
+            }
+            self.segments.dedup_by(|next, segment| next.1 == segment.1);
+        }
+
+        /// Finds the lowest spot where the width fits, preferring the left one, occupies the width
+        /// plus the gap and the height there and returns the left and top offset.
+        pub fn place(&mut self, width: u32, gap: u32, height: u32) -> (u32, u32) {
+            let width = width.min(self.width);
+            let (left, top) = self
+                .segments
+                .iter()
+                .map(|&(left, _)| left)
+                .filter(|&left| left + width <= self.width)
+                .map(|left| (left, self.height(left, left + width.max(1))))
+                .min_by_key(|&(_, top)| top)
+                .unwrap_or_default();
+            self.raise(left, width.saturating_add(gap), top.saturating_add(height));
+            (left, top)
+        }
+
+        /// Returns the height of the highest segment between left and right.
+        fn height(&self, left: u32, right: u32) -> u32 {
+            let mut segments = self.segments.iter().peekable();
+            let mut height = 0;
+            while let Some(&(start, segment_height)) = segments.next() {
+                let end = segments.peek().map_or(self.width, |next| next.0);
+                if start < right && end > left {
+                    height = height.max(segment_height);
+                }
+            }
+            height
+        }
+
+        /// Splits the segment containing the offset, so that a segment starts there.
+        fn split(&mut self, offset: u32) {
+            if offset >= self.width {
+                return;
+            }
+            let index = self.segments.partition_point(|&(left, _)| left <= offset);
+            let (left, height) = self.segments[index - 1];
+            if left != offset {
+                self.segments.insert(index, (offset, height));
+            }
+        }
+    }
+}
+
+/// Packing of items that span multiple cells.
+mod spans {
+    use alloc::{vec, vec::Vec};
+
+    /// Occupied cells of a grid that grows downwards.
+    pub struct Grid {
+        n_columns: usize,
+        occupied: Vec<bool>,
+        /// Index of the first cell that is not occupied.
+        first_free: usize,
+    }
+
+    impl Grid {
+        pub fn new(n_columns: usize) -> Self {
+            Grid {
+                n_columns,
+                occupied: Vec::new(),
+                first_free: 0,
+            }
+        }
+
+        /// Occupies the first free area of the size in row-major order and returns its row and
+        /// column.
+        pub fn place(&mut self, columns: usize, rows: usize) -> (usize, usize) {
+            let mut cell = self.first_free;
+            loop {
+                let (row, column) = (cell / self.n_columns, cell % self.n_columns);
+                if column + columns <= self.n_columns && self.is_free(row, column, columns, rows) {
+                    let len = (row + rows) * self.n_columns;
+                    if self.occupied.len() < len {
+                        self.occupied.resize(len, false);
+                    }
+                    for row in row..row + rows {
+                        let start = row * self.n_columns + column;
+                        self.occupied[start..start + columns].fill(true);
+                    }
+                    while self.occupied.get(self.first_free) == Some(&true) {
+                        self.first_free += 1;
+                    }
+                    return (row, column);
+                }
+                cell += 1;
+            }
+        }
+
+        /// Returns the number of rows that contain an occupied cell.
+        pub fn rows(&self) -> usize {
+            self.occupied
+                .iter()
+                .rposition(|&occupied| occupied)
+                .map_or(0, |cell| cell / self.n_columns + 1)
+        }
+
+        /// Returns whether all cells of the area are free, including the ones below the grid.
+        fn is_free(&self, row: usize, column: usize, columns: usize, rows: usize) -> bool {
+            (row..row + rows).all(|row| {
+                let start = row * self.n_columns + column;
+                self.occupied
+                    .iter()
+                    .skip(start)
+                    .take(columns)
+                    .all(|&occupied| !occupied)
+            })
+        }
+    }
+
+    /// Heights of the columns of a vertical masonry layout and the holes below items that span
+    /// multiple columns of different heights.
+    pub struct Columns {
+        heights: Vec<u32>,
+        /// Free space in a column as column, top and bottom.
+        holes: Vec<(usize, u32, u32)>,
+    }
+
+    impl Columns {
+        pub fn new(n_columns: usize, top: u32) -> Self {
+            Columns {
+                heights: vec![top; n_columns],
+                holes: Vec::new(),
+            }
+        }
+
+        /// Occupies the height in adjacent columns and returns the top offset and first column.
+        pub fn place(&mut self, span: usize, height: u32) -> (u32, usize) {
+            let (column, top) = (0..=self.heights.len() - span)
+                .map(|column| {
+                    let top = self.heights[column..column + span].iter().max();
+                    (column, top.copied().unwrap_or_default())
+                })
+                .min_by_key(|&(_, top)| top)
+                .unwrap_or_default();
+
+            if span == 1 {
+                let hole = self
+                    .holes
+                    .iter_mut()
+                    .filter(|(_, hole_top, bottom)| *hole_top + height <= *bottom)
+                    .min_by_key(|(_, hole_top, _)| *hole_top);
+                if let Some((column, hole_top, _)) = hole {
+                    if *hole_top < top {
+                        let placed = (*hole_top, *column);
+                        *hole_top += height;
+                        return placed;
+                    }
+                }
+            }
+
+            for (index, column_height) in self.heights[column..column + span].iter_mut().enumerate()
+            {
+                if *column_height < top {
+                    self.holes.push((column + index, *column_height, top));
+                }
+                *column_height = top + height;
+            }
+            (top, column)
+        }
+
+        pub fn max_height(&self) -> u32 {
+            self.heights.iter().copied().max().unwrap_or_default()
+        }
+    }
+}
+
+/// Lookup table to find items by their vertical position.
+///
+/// The container is divided into bands of equal height. For every band the smallest range of item
+/// indices is stored that contains all items intersecting with the band. In horizontal and grid
+/// layouts items are sorted by their top offset, so the ranges are tight. In the vertical masonry
+/// layout items are roughly sorted, so the ranges are slightly larger than needed.
+mod spatial_index {
+    use alloc::vec::Vec;
+    use core::ops::Range;
+
+    use super::Transform;
+
+    /// Maximum number of bands, so that very tall containers use taller bands instead of a lot of
+    /// memory.
+    const MAX_BANDS: u32 = 1 << 16;
+
+    /// Range of a band without items, so that the first item replaces both ends.
+    const NO_ITEMS: Range<u32> = Range {
+        start: u32::MAX,
+        end: 0,
+    };
+
+    #[derive(Default)]
+    pub struct SpatialIndex {
+        band_height: u32,
+        bands: Vec<Range<u32>>,
+    }
+
+    impl SpatialIndex {
+        pub fn build(&mut self, transforms: &[Transform], height: u32, band_height: u32) {
+            let band_height = band_height.max(height / MAX_BANDS).max(1);
+            self.band_height = band_height;
+            self.bands.clear();
+            self.bands
+                .resize((height / band_height) as usize + 1, NO_ITEMS);
+
+            for (index, transform) in (0_u32..).zip(transforms) {
+                let top = transform.0.get::<2>();
+                let bottom = top.saturating_add(transform.0.get::<1>().max(1) - 1);
+                let first = (top / band_height) as usize;
+                let last = ((bottom / band_height) as usize).min(self.bands.len() - 1);
+                for band in self.bands.get_mut(first..=last).unwrap_or_default() {
+                    band.start = band.start.min(index);
+                    band.end = band.end.max(index + 1);
+                }
+            }
+        }
+
+        /// Returns a range of item indices that contains at least all items which intersect with
+        /// the vertical range between top and bottom (exclusive).
+        pub fn query(&self, top: u32, bottom: u32) -> Range<usize> {
+            if top >= bottom || self.bands.is_empty() {
+                return 0..0;
+            }
+            let first = (top / self.band_height) as usize;
+            let last = (((bottom - 1) / self.band_height) as usize).min(self.bands.len() - 1);
+
+            let (start, end) = self
+                .bands
+                .get(first..=last)
+                .unwrap_or_default()
+                .iter()
+                .fold((u32::MAX, 0), |(start, end), band| {
+                    (start.min(band.start), end.max(band.end))
+                });
+            if start < end {
+                start as usize..end as usize
+            } else {
+                0..0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1274 @@
+//! Property tests for the layouts.
+//!
+//! Every test runs on a few hundred random layouts, which are generated by a small xorshift
+//! generator with a fixed seed, so that failures can be reproduced.
+use core::cell::Cell;
+
+use alloc::{vec, vec::Vec};
+
+use super::{Layout, Transform, MIN_ITEMS_CAPACITY};
+use crate::data::{ColumnMode, Direction, LastRow, MasonryType, NavigationDirection};
+
+const KINDS: [MasonryType; 7] = [
+    MasonryType::Vertical,
+    MasonryType::Horizontal,
+    MasonryType::Grid,
+    MasonryType::Justified,
+    MasonryType::VerticalOrdered,
+    MasonryType::VerticalBalanced,
+    MasonryType::Freeform,
+];
+
+const VERTICAL_KINDS: [MasonryType; 3] = [
+    MasonryType::Vertical,
+    MasonryType::VerticalOrdered,
+    MasonryType::VerticalBalanced,
+];
+
+const LAST_ROWS: [LastRow; 4] = [
+    LastRow::Keep,
+    LastRow::Justify,
+    LastRow::Center,
+    LastRow::Balance,
+];
+
+const DIRECTIONS: [Direction; 3] = [
+    Direction::LeftToRight,
+    Direction::RightToLeft,
+    Direction::TopToBottom,
+];
+
+const NAVIGATION_DIRECTIONS: [NavigationDirection; 4] = [
+    NavigationDirection::Up,
+    NavigationDirection::Down,
+    NavigationDirection::Left,
+    NavigationDirection::Right,
+];
+
+const TRIALS: usize = 300;
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 32) as u32
+    }
+
+    /// Returns a number in the range `start..end`.
+    fn range(&mut self, start: u32, end: u32) -> u32 {
+        start + self.next() % (end - start)
+    }
+
+    /// Returns a number of a smaller type in the range `start..end`.
+    fn range_of<T: Into<u32> + TryFrom<u32>>(&mut self, start: T, end: T) -> T {
+        T::try_from(self.range(start.into(), end.into()))
+            .ok()
+            .unwrap()
+    }
+
+    /// Returns an index in the range `0..len`.
+    fn index(&mut self, len: usize) -> usize {
+        self.next() as usize % len
+    }
+
+    fn pick<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.next() as usize % values.len()]
+    }
+}
+
+/// Everything needed to create and compute a layout.
+#[derive(Clone)]
+struct Scenario {
+    kind: MasonryType,
+    container_width: u32,
+    thumbnail_size: u32,
+    column_gap: u16,
+    row_gap: u16,
+    inset: u16,
+    caption_height: u16,
+    column_mode: (ColumnMode, u32, u32),
+    max_row_deviation: u8,
+    last_row: LastRow,
+    direction: Direction,
+    max_aspect_ratio: f32,
+    dimensions: Vec<(u16, u16)>,
+    spans: Vec<(usize, u8, u8)>,
+    groups: Vec<(usize, u16)>,
+}
+
+impl Scenario {
+    fn random(rng: &mut Rng) -> Scenario {
+        let num_items = rng.range(1, 150) as usize;
+        let dimensions = (0..num_items)
+            .map(|_| match rng.range(0, 10) {
+                // Degenerate and extreme dimensions
+                0 => (rng.range_of(0, 3), rng.range_of(0, 3)),
+                1 => (rng.range_of(1, 100), u16::MAX),
+                2 => (u16::MAX, rng.range_of(1, 100)),
+                _ => (rng.range_of(100, 6000), rng.range_of(100, 6000)),
+            })
+            .collect();
+        let mut groups: Vec<_> = (0..rng.range(0, 4))
+            .map(|_| (rng.index(num_items + 1), rng.range_of(0, 60)))
+            .collect();
+        groups.sort_unstable();
+        let spans = (0..rng.range(0, 4))
+            .map(|_| (rng.index(num_items), rng.range_of(1, 4), rng.range_of(1, 4)))
+            .collect();
+
+        let mut scenario = Scenario {
+            kind: rng.pick(&KINDS),
+            container_width: match rng.range(0, 10) {
+                0 => rng.range(65_536, 200_000),
+                _ => rng.range(50, 4000),
+            },
+            thumbnail_size: match rng.range(0, 10) {
+                0 => rng.range(1000, 8000),
+                _ => rng.range(20, 600),
+            },
+            column_gap: rng.range_of(0, 20),
+            row_gap: rng.range_of(0, 20),
+            inset: rng.pick(&[0, 0, 5, 24]),
+            caption_height: rng.pick(&[0, 0, 0, 30]),
+            column_mode: match rng.range(0, 8) {
+                0 => (ColumnMode::Fixed, rng.range(0, 12), 0),
+                1 => (ColumnMode::Fit, rng.range(0, 12), 0),
+                2 => {
+                    let min_width = rng.range(0, 500);
+                    (
+                        ColumnMode::Width,
+                        min_width,
+                        rng.pick(&[0, min_width + 100]),
+                    )
+                }
+                _ => (ColumnMode::Auto, 0, 0),
+            },
+            max_row_deviation: rng.range_of(0, 60),
+            last_row: rng.pick(&LAST_ROWS),
+            direction: Direction::LeftToRight,
+            max_aspect_ratio: rng.pick(&[3.0, 0.0, 1.5, 10.0]),
+            dimensions,
+            spans,
+            groups,
+        };
+        // Unlimited aspect ratios in very wide columns would exceed the u32 coordinates.
+        if scenario.container_width > 65_535 && scenario.max_aspect_ratio == 0.0 {
+            scenario.max_aspect_ratio = 10.0;
+        }
+        scenario
+    }
+
+    /// Returns the container width including the inset. Containers narrower than one thumbnail
+    /// without the inset are treated as wide as one thumbnail.
+    fn outer_width(&self) -> u32 {
+        let inset = 2 * u32::from(self.inset);
+        self.container_width
+            .saturating_sub(inset)
+            .max(self.thumbnail_size)
+            + inset
+    }
+
+    fn layout(&self) -> Layout {
+        let mut layout = Layout::new(self.dimensions.len(), self.thumbnail_size, self.column_gap);
+        layout.set_gaps(self.column_gap, self.row_gap);
+        layout.set_inset(self.inset);
+        layout.set_caption_height(self.caption_height);
+        let (column_mode, first, second) = self.column_mode;
+        layout.set_column_mode(column_mode, first, second);
+        layout.set_max_row_deviation(self.max_row_deviation);
+        layout.set_last_row(self.last_row);
+        layout.set_direction(self.direction);
+        layout.set_max_aspect_ratio(self.max_aspect_ratio);
+        for (index, &(width, height)) in self.dimensions.iter().enumerate() {
+            layout.set_dimension(index, width, height);
+        }
+        for &(index, columns, rows) in &self.spans {
+            layout.set_span(index, columns, rows);
+        }
+        for &(start, header_height) in &self.groups {
+            layout.add_group(start, header_height);
+        }
+        layout
+    }
+}
+
+fn compute(
+    layout: &mut Layout,
+    kind: MasonryType,
+    container_width: u32,
+    is_cancelled: &dyn Fn() -> bool,
+) -> Option<u32> {
+    match kind {
+        MasonryType::Vertical => layout.compute_vertical(container_width, is_cancelled),
+        MasonryType::Horizontal => layout.compute_horizontal(container_width, is_cancelled),
+        MasonryType::Grid => layout.compute_grid(container_width, is_cancelled),
+        MasonryType::Justified => layout.compute_justified(container_width, is_cancelled),
+        MasonryType::VerticalOrdered => {
+            layout.compute_vertical_ordered(container_width, is_cancelled)
+        }
+        MasonryType::VerticalBalanced => {
+            layout.compute_vertical_balanced(container_width, is_cancelled)
+        }
+        MasonryType::Freeform => layout.compute_freeform(container_width, is_cancelled),
+    }
+}
+
+fn compute_all(layout: &mut Layout, kind: MasonryType, container_width: u32) -> u32 {
+    compute(layout, kind, container_width, &|| false).unwrap()
+}
+
+fn restore(
+    layout: &mut Layout,
+    snapshot: &[u8],
+    kind: MasonryType,
+    container_width: u32,
+) -> Option<u32> {
+    let snapshot = layout.read_snapshot(snapshot, kind, container_width)?;
+    Some(layout.apply_snapshot(snapshot))
+}
+
+/// Returns width, height, top and left of the transform.
+fn rect(transform: &Transform) -> [u32; 4] {
+    transform.0.to_array()
+}
+
+fn items(layout: &Layout) -> Vec<[u32; 4]> {
+    layout.transforms[..layout.num_items]
+        .iter()
+        .map(rect)
+        .collect()
+}
+
+fn headers(layout: &Layout) -> Vec<[u32; 4]> {
+    layout.header_transforms.iter().map(rect).collect()
+}
+
+fn overlap(a: [u32; 4], b: [u32; 4]) -> bool {
+    let [a_width, a_height, a_top, a_left] = a;
+    let [b_width, b_height, b_top, b_left] = b;
+    a_left < b_left + b_width
+        && b_left < a_left + a_width
+        && a_top < b_top + b_height
+        && b_top < a_top + a_height
+}
+
+#[test]
+fn items_do_not_overlap() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..TRIALS {
+        let scenario = Scenario::random(&mut rng);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let rects: Vec<_> = items(&layout)
+            .into_iter()
+            .chain(headers(&layout))
+            .filter(|&[width, height, ..]| width > 0 && height > 0)
+            .collect();
+        for (i, &a) in rects.iter().enumerate() {
+            for &b in &rects[i + 1..] {
+                assert!(!overlap(a, b), "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn items_stay_inside_container() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..TRIALS {
+        let scenario = Scenario::random(&mut rng);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let container_width = scenario.outer_width();
+        let inset = u32::from(scenario.inset);
+        for [width, _, _, left] in items(&layout) {
+            assert!(left >= inset);
+            assert!(left + width <= container_width - inset);
+        }
+    }
+}
+
+#[test]
+fn height_contains_all_items() {
+    let mut rng = Rng(0x1234_5678_9ABC_DEF1);
+    for _ in 0..TRIALS {
+        let scenario = Scenario::random(&mut rng);
+        let mut layout = scenario.layout();
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let bottom = items(&layout)
+            .into_iter()
+            .chain(headers(&layout))
+            .map(|[_, height, top, _]| top + height)
+            .max()
+            .unwrap_or(0);
+        // The height ends at the last row without a gap below it.
+        assert_eq!(height, bottom + u32::from(scenario.inset));
+    }
+}
+
+#[test]
+fn right_to_left_mirrors_left_to_right() {
+    let mut rng = Rng(0x6A09_E667_F3BC_C908);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        let mut expected = scenario.layout();
+        let expected_height = compute_all(&mut expected, scenario.kind, scenario.container_width);
+        scenario.direction = Direction::RightToLeft;
+        let mut layout = scenario.layout();
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let container_width = scenario.outer_width();
+        let mirrored: Vec<_> = items(&expected)
+            .into_iter()
+            .map(|[width, height, top, left]| [width, height, top, container_width - left - width])
+            .collect();
+        assert_eq!(height, expected_height);
+        assert_eq!(items(&layout), mirrored);
+    }
+}
+
+#[test]
+fn top_to_bottom_transposes_left_to_right() {
+    let mut rng = Rng(0xBB67_AE85_84CA_A73B);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = Direction::TopToBottom;
+        let mut layout = scenario.layout();
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+        scenario.direction = Direction::LeftToRight;
+        for dimension in &mut scenario.dimensions {
+            *dimension = (dimension.1, dimension.0);
+        }
+        let mut expected = scenario.layout();
+        let expected_height = compute_all(&mut expected, scenario.kind, scenario.container_width);
+
+        let transposed: Vec<_> = items(&expected)
+            .into_iter()
+            .map(|[width, height, top, left]| [height, width, left, top])
+            .collect();
+        assert_eq!(height, expected_height);
+        assert_eq!(items(&layout), transposed);
+
+        let range = layout.items_in_range(0, height / 2);
+        assert_eq!(range, expected.items_in_range(0, height / 2));
+    }
+}
+
+#[test]
+fn columns_have_equal_widths_in_vertical_and_grid_layouts() {
+    let mut rng = Rng(0x0DDB_1A5E_5BAD_5EED);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.kind = rng.pick(&[
+            MasonryType::Vertical,
+            MasonryType::Grid,
+            MasonryType::VerticalOrdered,
+            MasonryType::VerticalBalanced,
+        ]);
+        scenario.spans.clear();
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let transforms = items(&layout);
+        let width = transforms[0][0];
+        assert!(transforms
+            .iter()
+            .all(|&[item_width, ..]| item_width == width));
+        if scenario.kind == MasonryType::Grid {
+            let height = width + u32::from(scenario.caption_height);
+            assert!(transforms
+                .iter()
+                .all(|&[_, item_height, ..]| item_height == height));
+        }
+    }
+}
+
+#[test]
+fn ordered_vertical_layout_keeps_reading_order() {
+    let mut layout = Layout::new(7, 100, 0);
+    for index in 0..7 {
+        layout.set_dimension(index, 100, [100, 150, 120][index % 3]);
+    }
+    layout.set_dimension(6, 100, 300);
+    compute_all(&mut layout, MasonryType::VerticalOrdered, 300);
+
+    let columns: Vec<_> = items(&layout).iter().map(|[.., left]| left / 100).collect();
+    assert_eq!(columns[..6], [0, 1, 2, 0, 1, 2]);
+
+    // The first column is more than one column width taller than the others, so it is skipped.
+    layout.set_dimension(0, 100, 300);
+    compute_all(&mut layout, MasonryType::VerticalOrdered, 300);
+    let columns: Vec<_> = items(&layout).iter().map(|[.., left]| left / 100).collect();
+    assert_eq!(columns, [0, 1, 2, 2, 1, 2, 0]);
+}
+
+#[test]
+fn vertical_layouts_keep_columns_balanced() {
+    let mut rng = Rng(0x243F_6A88_85A3_08D3);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.kind = rng.pick(&VERTICAL_KINDS);
+        scenario.spans.clear();
+        scenario.groups.clear();
+        scenario.inset = 0;
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let items = items(&layout);
+        let column_width = items[0][0] + u32::from(scenario.column_gap);
+        let tallest = items.iter().map(|&[_, height, ..]| height).max().unwrap();
+        let n_columns =
+            1 + items.iter().map(|&[.., left]| left).max().unwrap() / column_width.max(1);
+        let mut bottoms = vec![None; n_columns as usize];
+        for &[_, height, top, left] in &items {
+            let bottom = &mut bottoms[(left / column_width.max(1)) as usize];
+            *bottom = Some(bottom.unwrap_or(0).max(top + height));
+        }
+        // Columns stay empty if there are fewer items than columns.
+        if let Some(bottoms) = bottoms.into_iter().collect::<Option<Vec<_>>>() {
+            let imbalance = bottoms.iter().max().unwrap() - bottoms.iter().min().unwrap();
+            let gap = u32::from(scenario.row_gap);
+            let limit = match scenario.kind {
+                // The next column may be one column width taller than the shortest one.
+                MasonryType::VerticalOrdered => column_width + tallest + gap,
+                _ => tallest + gap,
+            };
+            assert!(imbalance <= limit, "{imbalance} > {limit}");
+        }
+    }
+}
+
+#[test]
+fn rows_fill_the_container_exactly() {
+    let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.kind = rng.pick(&[MasonryType::Horizontal, MasonryType::Justified]);
+        scenario.last_row = LastRow::Justify;
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let container_width = scenario.outer_width() - u32::from(scenario.inset);
+        let items = items(&layout);
+        let mut start = 0;
+        for end in 1..=items.len() {
+            if end < items.len() && items[end][2] == items[start][2] {
+                continue;
+            }
+            // Justified rows of a single item are not stretched beyond the maximum row deviation.
+            // Rows without height share their top with the next row.
+            if scenario.kind == MasonryType::Horizontal || end - start > 1 {
+                let right = items[start..end]
+                    .iter()
+                    .map(|[width, _, _, left]| left + width);
+                assert_eq!(right.max(), Some(container_width));
+            }
+            start = end;
+        }
+    }
+}
+
+#[test]
+fn aspect_ratios_are_precise_and_clamped_per_layout() {
+    let mut layout = Layout::new(3, 900, 0);
+    layout.set_dimension(0, 1600, 900);
+    layout.set_dimension(1, 1700, 900);
+    layout.set_dimension(2, 8000, 1000);
+    assert_eq!(layout.natural_width(0), 1600);
+    assert_eq!(layout.natural_width(1), 1700);
+    // Panoramas are cropped to three times their height by default.
+    assert_eq!(layout.natural_width(2), 2700);
+
+    layout.set_max_aspect_ratio(0.0);
+    assert_eq!(layout.natural_width(2), 7200);
+    layout.set_max_aspect_ratio(2.0);
+    assert_eq!(layout.natural_width(2), 1800);
+}
+
+#[test]
+fn large_thumbnails_and_containers_are_not_limited() {
+    let mut layout = Layout::new(20, 4000, 0);
+    compute_all(&mut layout, MasonryType::Grid, 100_000);
+    assert_eq!(items(&layout)[0], [4000, 4000, 0, 0]);
+    assert_eq!(items(&layout)[19], [4000, 4000, 0, 76_000]);
+
+    let mut layout = Layout::new(20, 3500, 0);
+    let height = compute_all(&mut layout, MasonryType::Horizontal, 70_000);
+    assert_eq!(height, 3500);
+    assert_eq!(items(&layout)[19], [3500, 3500, 0, 66_500]);
+}
+
+#[test]
+fn gaps_and_inset_are_independent() {
+    let mut layout = Layout::new(4, 100, 0);
+    layout.set_gaps(10, 4);
+    layout.set_inset(6);
+    let height = compute_all(&mut layout, MasonryType::Grid, 332);
+
+    assert_eq!(items(&layout)[0], [100, 100, 6, 6]);
+    assert_eq!(items(&layout)[2], [100, 100, 6, 226]);
+    assert_eq!(items(&layout)[3], [100, 100, 110, 6]);
+    // There is no row gap below the last row.
+    assert_eq!(height, 6 + 100 + 4 + 100 + 6);
+}
+
+#[test]
+fn column_modes_choose_number_and_width_of_columns() {
+    let columns = |mode: ColumnMode, first: u32, second: u32| {
+        let mut layout = Layout::new(20, 100, 0);
+        layout.set_column_mode(mode, first, second);
+        compute_all(&mut layout, MasonryType::Grid, 1000);
+        let items = items(&layout);
+        let n_columns = items.iter().filter(|&&[_, _, top, _]| top == 0).count();
+        (n_columns, items[0][0])
+    };
+    assert_eq!(columns(ColumnMode::Auto, 0, 0), (10, 100));
+    assert_eq!(columns(ColumnMode::Fixed, 3, 0), (3, 100));
+    assert_eq!(columns(ColumnMode::Fixed, 20, 0), (20, 50));
+    assert_eq!(columns(ColumnMode::Fit, 4, 0), (4, 250));
+    assert_eq!(columns(ColumnMode::Width, 150, 0), (6, 166));
+    assert_eq!(columns(ColumnMode::Width, 150, 160), (6, 160));
+}
+
+#[test]
+fn smaller_items_fill_holes_left_by_spans() {
+    let mut layout = Layout::new(4, 100, 0);
+    layout.set_span(1, 3, 1);
+    compute_all(&mut layout, MasonryType::Grid, 300);
+
+    // The wide item does not fit next to the first one, so the next items move up.
+    assert_eq!(items(&layout)[1], [300, 100, 100, 0]);
+    assert_eq!(items(&layout)[2], [100, 100, 0, 100]);
+    assert_eq!(items(&layout)[3], [100, 100, 0, 200]);
+
+    let mut layout = Layout::new(4, 100, 0);
+    for index in 0..4 {
+        layout.set_dimension(index, 100, 100);
+    }
+    layout.set_dimension(0, 100, 200);
+    layout.set_span(1, 2, 1);
+    compute_all(&mut layout, MasonryType::Vertical, 200);
+
+    // The item spanning both columns leaves a hole next to the tall first item.
+    assert_eq!(items(&layout)[1], [200, 200, 200, 0]);
+    assert_eq!(items(&layout)[2], [100, 100, 0, 100]);
+    assert_eq!(items(&layout)[3], [100, 100, 100, 100]);
+}
+
+#[test]
+fn new_items_do_not_keep_the_spans_of_removed_items() {
+    let mut layout = Layout::new(10, 100, 0);
+    layout.set_span(7, 3, 3);
+    layout.resize(5);
+    // Spans of items that do not exist are ignored.
+    layout.set_span(8, 3, 3);
+    // The new items exceed the allocated capacity.
+    layout.resize(2 * MIN_ITEMS_CAPACITY);
+    compute_all(&mut layout, MasonryType::Grid, 300);
+
+    let expected: Vec<_> = (0_u32..)
+        .take(2 * MIN_ITEMS_CAPACITY)
+        .map(|index| [100, 100, index / 3 * 100, index % 3 * 100])
+        .collect();
+    assert_eq!(items(&layout), expected);
+}
+
+#[test]
+fn incremental_recompute_matches_full_recompute() {
+    let mut rng = Rng(0xC0FF_EE00_DEAD_BEEF);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = rng.pick(&DIRECTIONS);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        for _ in 0..4 {
+            match rng.range(0, 4) {
+                0 => {
+                    let index = rng.index(scenario.dimensions.len());
+                    let dimension = (rng.range_of(1, 6000), rng.range_of(1, 6000));
+                    scenario.dimensions[index] = dimension;
+                    layout.set_dimension(index, dimension.0, dimension.1);
+                }
+                1 => {
+                    let dimension = (rng.range_of(1, 6000), rng.range_of(1, 6000));
+                    scenario.dimensions.push(dimension);
+                    layout.resize(scenario.dimensions.len());
+                    layout.set_dimension(scenario.dimensions.len() - 1, dimension.0, dimension.1);
+                }
+                2 => {
+                    let len = rng.index(scenario.dimensions.len()) + 1;
+                    scenario.dimensions.truncate(len);
+                    scenario.spans.retain(|&(index, ..)| index < len);
+                    layout.resize(len);
+                }
+                _ => {
+                    let index = rng.index(scenario.dimensions.len());
+                    let span = (index, rng.range_of(1, 4), rng.range_of(1, 4));
+                    scenario.spans.push(span);
+                    layout.set_span(span.0, span.1, span.2);
+                }
+            }
+            let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+            let mut expected = scenario.layout();
+            let expected_height =
+                compute_all(&mut expected, scenario.kind, scenario.container_width);
+            assert_eq!(
+                height, expected_height,
+                "{} {:?} {:?}",
+                scenario.kind as u8, scenario.direction as u8, scenario.column_mode.1
+            );
+            assert_eq!(items(&layout), items(&expected));
+            assert_eq!(headers(&layout), headers(&expected));
+        }
+    }
+}
+
+#[test]
+fn splitting_a_group_with_spans_recomputes_the_items_in_front() {
+    let mut rng = Rng(0x5851_F42D_4C95_7F2D);
+    let dimensions: Vec<_> = (0..100)
+        .map(|_| (rng.range_of(100, 6000), rng.range_of(100, 6000)))
+        .collect();
+    let layout = |groups: &[(usize, u16)]| {
+        let mut layout = Layout::new(dimensions.len(), 200, 8);
+        layout.set_direction(Direction::RightToLeft);
+        for (index, &(width, height)) in dimensions.iter().enumerate() {
+            layout.set_dimension(index, width, height);
+        }
+        layout.set_span(72, 3, 3);
+        for &(start, header_height) in groups {
+            layout.add_group(start, header_height);
+        }
+        layout
+    };
+    let kind = MasonryType::VerticalOrdered;
+    let mut split = layout(&[(15, 0)]);
+    compute_all(&mut split, kind, 1000);
+
+    // The group before the new one has no spans anymore, so its items are laid out in order.
+    split.add_group(36, 42);
+    let height = compute_all(&mut split, kind, 1000);
+    let mut expected = layout(&[(15, 0), (36, 42)]);
+    assert_eq!(height, compute_all(&mut expected, kind, 1000));
+    assert_eq!(items(&split), items(&expected));
+}
+
+#[test]
+fn cancelled_computation_is_completed_by_the_next_one() {
+    let mut rng = Rng(0xFEED_FACE_CAFE_BEEF);
+    for _ in 0..TRIALS {
+        let scenario = Scenario::random(&mut rng);
+        let mut layout = scenario.layout();
+
+        let checks = rng.range(0, 20);
+        let count = Cell::new(0);
+        let is_cancelled = || {
+            count.set(count.get() + 1);
+            count.get() > checks
+        };
+        compute(
+            &mut layout,
+            scenario.kind,
+            scenario.container_width,
+            &is_cancelled,
+        );
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let mut expected = scenario.layout();
+        let expected_height = compute_all(&mut expected, scenario.kind, scenario.container_width);
+        assert_eq!(height, expected_height);
+        assert_eq!(items(&layout), items(&expected));
+    }
+}
+
+#[test]
+fn previous_transforms_are_retained_for_animations() {
+    let mut rng = Rng(0x1B87_3593_CC9E_2D51);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        let mut layout = scenario.layout();
+        layout.set_retain_previous(true);
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        let mut previous = items(&layout);
+
+        // A cancelled computation in between does not replace the previous transforms. Small
+        // layouts can be finished before the cancellation is checked.
+        scenario.thumbnail_size += rng.range(1, 100);
+        layout.set_thumbnail_size(scenario.thumbnail_size);
+        if compute(
+            &mut layout,
+            scenario.kind,
+            scenario.container_width,
+            &|| true,
+        )
+        .is_some()
+        {
+            previous = items(&layout);
+        }
+        let len = previous.len() + rng.range(0, 3) as usize;
+        layout.resize(len);
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        let current = items(&layout);
+
+        for (index, &end) in current.iter().enumerate() {
+            let at = |t| rect(&layout.interpolate(index, t).unwrap());
+            match previous.get(index) {
+                Some(&start) => {
+                    assert_eq!(rect(layout.get_previous_transform(index).unwrap()), start);
+                    assert_eq!(at(0.0), start);
+                    let middle = at(0.5);
+                    for ((start, end), middle) in start.into_iter().zip(end).zip(middle) {
+                        // f32 has 24 bits of precision, so large coordinates are approximated.
+                        let tolerance = 1 + (start.max(end) >> 22);
+                        let expected =
+                            u32::try_from((u64::from(start) + u64::from(end)) / 2).unwrap();
+                        assert!(middle.abs_diff(expected) <= tolerance);
+                    }
+                }
+                None => {
+                    assert!(layout.get_previous_transform(index).is_none());
+                    assert_eq!(at(0.0), end);
+                }
+            }
+            assert_eq!(at(1.0), end);
+        }
+        assert!(layout.interpolate(len, 0.5).is_none());
+
+        // A computation without any change does not move the items.
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        let previous: Vec<_> = (0..len)
+            .map(|index| rect(layout.get_previous_transform(index).unwrap()))
+            .collect();
+        assert_eq!(previous, current);
+    }
+}
+
+#[test]
+fn neighbors_are_the_nearest_items_in_the_direction() {
+    let mut rng = Rng(0x94D0_49BB_1331_11EB);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = rng.pick(&DIRECTIONS);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let rects = items(&layout);
+        // Gap to the item in the direction and on the other axis, or `None` if it is not beyond.
+        let gaps = |[width, height, top, left]: [u32; 4], other: [u32; 4], direction| {
+            let [other_width, other_height, other_top, other_left] = other;
+            let gap = |start: u32, end: u32, other_start: u32, other_end: u32| {
+                other_start.max(start).saturating_sub(other_end.min(end))
+            };
+            let vertical = gap(top, top + height, other_top, other_top + other_height);
+            let horizontal = gap(left, left + width, other_left, other_left + other_width);
+            let (beyond, gap, cross_gap) = match direction {
+                NavigationDirection::Up => (other_top + other_height <= top, vertical, horizontal),
+                NavigationDirection::Down => (other_top >= top + height, vertical, horizontal),
+                NavigationDirection::Left => {
+                    (other_left + other_width <= left, horizontal, vertical)
+                }
+                NavigationDirection::Right => (other_left >= left + width, horizontal, vertical),
+            };
+            let visible = other_width > 0 && other_height > 0;
+            (beyond && visible).then(|| (gap, cross_gap))
+        };
+        let index = rng.index(rects.len());
+        for direction in NAVIGATION_DIRECTIONS {
+            let candidates: Vec<_> = rects
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .filter_map(|(other, &rect)| Some((other, gaps(rects[index], rect, direction)?)))
+                .collect();
+            match layout.neighbor(index, direction) {
+                Some(neighbor) => {
+                    let &(_, (gap, cross_gap)) = candidates
+                        .iter()
+                        .find(|&&(other, _)| other == neighbor)
+                        .expect("the neighbor is beyond the item");
+                    for &(_, (other_gap, other_cross_gap)) in &candidates {
+                        if other_cross_gap == 0 {
+                            assert_eq!(cross_gap, 0);
+                            assert!(gap <= other_gap);
+                        }
+                    }
+                }
+                None => assert!(candidates.is_empty()),
+            }
+        }
+        assert!(layout
+            .neighbor(rects.len(), NavigationDirection::Down)
+            .is_none());
+    }
+}
+
+#[test]
+fn grid_neighbors_are_in_the_next_row_and_column() {
+    let mut rng = Rng(0xBF58_476D_1CE4_E5B9);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.kind = MasonryType::Grid;
+        scenario.spans.clear();
+        scenario.groups.clear();
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let len = scenario.dimensions.len();
+        let n_columns = items(&layout)
+            .iter()
+            .take_while(|&&[_, _, top, _]| top == u32::from(scenario.inset))
+            .count();
+        // Cells without an area are skipped by the navigation.
+        if items(&layout)[0][0] == 0 {
+            continue;
+        }
+        for index in 0..len {
+            let column = index % n_columns;
+            let below = index + n_columns;
+            // Below the incomplete last row, the nearest item is the last one diagonally.
+            let last_row = (len - 1) / n_columns;
+            let expected = if below < len {
+                Some(below)
+            } else {
+                (index / n_columns < last_row).then(|| len - 1)
+            };
+            assert_eq!(layout.neighbor(index, NavigationDirection::Down), expected);
+            assert_eq!(
+                layout.neighbor(index, NavigationDirection::Up),
+                index.checked_sub(n_columns)
+            );
+            // Right of the last item, the nearest item is the one diagonally above.
+            let expected = if column + 1 == n_columns {
+                None
+            } else if index + 1 < len {
+                Some(index + 1)
+            } else {
+                (index + 1).checked_sub(n_columns)
+            };
+            assert_eq!(layout.neighbor(index, NavigationDirection::Right), expected);
+            assert_eq!(
+                layout.neighbor(index, NavigationDirection::Left),
+                (column > 0).then(|| index - 1)
+            );
+        }
+    }
+}
+
+#[test]
+fn anchor_keeps_item_in_place() {
+    let mut rng = Rng(0x8CB9_2BA7_2F3D_8DD7);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = rng.pick(&DIRECTIONS);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        assert_eq!(layout.get_scroll_position(), None);
+
+        let main_top = |layout: &Layout, index: usize| {
+            let [_, _, top, left] = rect(layout.get_transform(index).unwrap());
+            match scenario.direction {
+                Direction::TopToBottom => left,
+                _ => top,
+            }
+        };
+        let index = rng.index(scenario.dimensions.len());
+        let scroll = rng.range(0, main_top(&layout, index) + 1000);
+        let offset =
+            i32::try_from(i64::from(main_top(&layout, index)) - i64::from(scroll)).unwrap();
+        layout.set_anchor(index, offset);
+
+        // The anchor is kept for the computation after a cancelled one.
+        layout.set_thumbnail_size(scenario.thumbnail_size + rng.range(1, 100));
+        if compute(
+            &mut layout,
+            scenario.kind,
+            scenario.container_width,
+            &|| true,
+        )
+        .is_none()
+        {
+            compute_all(&mut layout, scenario.kind, scenario.container_width);
+        }
+        let expected =
+            u32::try_from((i64::from(main_top(&layout, index)) - i64::from(offset)).max(0))
+                .unwrap();
+        assert_eq!(layout.get_scroll_position(), Some(expected));
+
+        // The anchor is only used once.
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        assert_eq!(layout.get_scroll_position(), None);
+    }
+}
+
+#[test]
+fn freeform_keeps_positions_and_places_other_items_around_them() {
+    let mut rng = Rng(0x2127_599B_F432_5C37);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.kind = MasonryType::Freeform;
+        scenario.groups.clear();
+        let snap = rng.pick(&[0, 1, 10, 64]);
+        let mut layout = scenario.layout();
+        layout.set_snap(snap);
+        let len = scenario.dimensions.len();
+        let mut positions = vec![None; len];
+        for _ in 0..rng.range(0, 10) {
+            let index = rng.index(len);
+            let position = [
+                rng.range(0, 5000),
+                rng.range(0, 1000),
+                rng.range(0, 3000),
+                rng.range(0, 5000),
+            ];
+            positions[index] = Some(position);
+            layout.set_position(index, Some(position));
+        }
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let inset = u32::from(scenario.inset);
+        let container_width = scenario.outer_width() - 2 * inset;
+        let snapped = |offset: u32| match u32::from(snap) {
+            0 => offset,
+            snap => (offset + snap / 2) / snap * snap,
+        };
+        let rects = items(&layout);
+        for ((index, &rect), &position) in rects.iter().enumerate().zip(&positions) {
+            match position {
+                Some([width, height, top, left]) => {
+                    let width = width.min(container_width);
+                    let left = snapped(left).min(container_width - width);
+                    assert_eq!(rect, [width, height, snapped(top) + inset, left + inset]);
+                }
+                None if rect[0] > 0 && rect[1] > 0 => {
+                    for (other, &other_rect) in rects.iter().enumerate() {
+                        let [width, height, ..] = other_rect;
+                        if other != index && width > 0 && height > 0 {
+                            assert!(
+                                !overlap(rect, other_rect),
+                                "{rect:?} overlaps {other_rect:?}"
+                            );
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // Without positions all items are placed automatically again.
+        for (index, _) in positions
+            .iter()
+            .enumerate()
+            .filter(|(_, position)| position.is_some())
+        {
+            layout.set_position(index, None);
+        }
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        let mut expected = scenario.layout();
+        compute_all(&mut expected, scenario.kind, scenario.container_width);
+        assert_eq!(items(&layout), items(&expected));
+    }
+}
+
+#[test]
+fn freeform_positions_at_the_limit_do_not_overflow() {
+    for direction in DIRECTIONS {
+        let mut layout = Layout::new(3, 100, 8);
+        layout.set_inset(24);
+        layout.set_direction(direction);
+        layout.set_position(0, Some([u32::MAX; 4]));
+        layout.set_position(1, Some([100, 100, u32::MAX - 50, 0]));
+        layout.set_dimension(2, 100, 100);
+        let height = compute_all(&mut layout, MasonryType::Freeform, 1000);
+
+        assert_eq!(height, u32::MAX);
+        assert!(layout.items_in_range(u32::MAX - 100, u32::MAX).contains(&1));
+    }
+}
+
+#[test]
+fn captions_are_added_below_the_images() {
+    let mut rng = Rng(0x4F1B_BCDC_BFA5_3E0B);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.caption_height = 0;
+        // Items that span multiple rows of the grid also cover the captions of the rows between.
+        if scenario.kind == MasonryType::Grid {
+            scenario.spans.clear();
+        }
+        let mut expected = scenario.layout();
+        compute_all(&mut expected, scenario.kind, scenario.container_width);
+        scenario.caption_height = rng.range_of(1, 100);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        // The images keep their size, even though the items can be placed differently.
+        let caption_height = u32::from(scenario.caption_height);
+        let images: Vec<_> = items(&layout)
+            .into_iter()
+            .map(|[width, height, ..]| [width, height - caption_height])
+            .collect();
+        let expected: Vec<_> = items(&expected)
+            .into_iter()
+            .map(|[width, height, ..]| [width, height])
+            .collect();
+        assert_eq!(images, expected);
+    }
+}
+
+#[test]
+fn items_in_range_contains_all_visible_items() {
+    let mut rng = Rng(0x5DEE_CE66_D1CE_4E5B);
+    for _ in 0..TRIALS {
+        let scenario = Scenario::random(&mut rng);
+        let mut layout = scenario.layout();
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let top = rng.range(0, height + 1);
+        let bottom = top + rng.range(1, 2000);
+        let range = layout.items_in_range(top, bottom);
+        let visible: Vec<_> = items(&layout)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, [_, item_height, item_top, _])| {
+                item_top < bottom && item_top + item_height > top
+            })
+            .map(|(index, _)| index)
+            .collect();
+        match (visible.first(), visible.last()) {
+            (Some(&first), Some(&last)) => assert_eq!((range.start, range.end), (first, last + 1)),
+            _ => assert!(range.is_empty()),
+        }
+    }
+}
+
+#[test]
+fn items_in_rect_are_all_intersecting_items() {
+    let mut rng = Rng(0xD6E8_FEB8_6659_FD93);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = rng.pick(&DIRECTIONS);
+        let mut layout = scenario.layout();
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+
+        let (width, height) = match scenario.direction {
+            Direction::TopToBottom => (height, scenario.outer_width()),
+            _ => (scenario.outer_width(), height),
+        };
+        let (x, y) = (rng.range(0, width + 1), rng.range(0, height + 1));
+        let (selection_width, selection_height) = (rng.range(1, 1000), rng.range(1, 1000));
+        let expected: Vec<_> = items(&layout)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, item)| overlap(item, [selection_width, selection_height, y, x]))
+            .map(|(index, _)| index)
+            .collect();
+        let actual: Vec<_> = layout
+            .items_in_rect(x, y, selection_width, selection_height)
+            .collect();
+        assert_eq!(actual, expected);
+
+        let expected = items(&layout)
+            .into_iter()
+            .position(|item| overlap(item, [1, 1, y, x]));
+        assert_eq!(layout.item_at(x, y), expected);
+    }
+}
+
+#[test]
+fn removed_items_are_not_found_after_all_items_are_removed() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = rng.pick(&DIRECTIONS);
+        let mut layout = scenario.layout();
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        let expected = items(&layout);
+
+        layout.resize(0);
+        assert_eq!(
+            compute_all(&mut layout, scenario.kind, scenario.container_width),
+            0
+        );
+        assert!(layout.items_in_range(0, u32::MAX).is_empty());
+        assert_eq!(layout.items_in_rect(0, 0, u32::MAX, u32::MAX).count(), 0);
+        assert_eq!(layout.item_at(10, 10), None);
+        for direction in NAVIGATION_DIRECTIONS {
+            assert_eq!(layout.neighbor(0, direction), None);
+        }
+
+        // The items are laid out from scratch when they are added again.
+        layout.resize(scenario.dimensions.len());
+        for (index, &(width, height)) in scenario.dimensions.iter().enumerate() {
+            layout.set_dimension(index, width, height);
+        }
+        for &(index, columns, rows) in &scenario.spans {
+            layout.set_span(index, columns, rows);
+        }
+        compute_all(&mut layout, scenario.kind, scenario.container_width);
+        assert_eq!(items(&layout), expected);
+    }
+}
+
+#[test]
+fn bulk_dimensions_match_single_dimensions() {
+    let mut rng = Rng(0xA076_1D64_78BD_642F);
+    for _ in 0..TRIALS {
+        let scenario = Scenario::random(&mut rng);
+        let layout = scenario.layout();
+        let dimensions: Vec<_> = scenario
+            .dimensions
+            .iter()
+            .flat_map(|&(width, height)| [width, height])
+            .collect();
+        let mut bulk = Layout::new(
+            scenario.dimensions.len(),
+            scenario.thumbnail_size,
+            scenario.column_gap,
+        );
+        bulk.set_dimensions(&dimensions);
+
+        assert!(layout.aspect_ratios[..layout.num_items] == bulk.aspect_ratios[..bulk.num_items]);
+    }
+}
+
+#[test]
+fn restored_snapshot_matches_computed_layout() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..TRIALS {
+        let mut scenario = Scenario::random(&mut rng);
+        scenario.direction = rng.pick(&DIRECTIONS);
+        let mut layout = scenario.layout();
+        let height = compute_all(&mut layout, scenario.kind, scenario.container_width);
+        let snapshot = layout.snapshot().unwrap();
+
+        let mut restored = Scenario {
+            dimensions: Vec::new(),
+            spans: Vec::new(),
+            groups: Vec::new(),
+            ..scenario.clone()
+        }
+        .layout();
+        let kind = rng.pick(&KINDS);
+        if kind != scenario.kind {
+            assert_eq!(
+                restore(&mut restored, &snapshot, kind, scenario.container_width),
+                None
+            );
+        }
+        assert_eq!(
+            restore(
+                &mut restored,
+                &snapshot,
+                scenario.kind,
+                scenario.container_width + 1
+            ),
+            None
+        );
+        let truncated = &snapshot[..rng.index(snapshot.len())];
+        assert_eq!(
+            restore(
+                &mut restored,
+                truncated,
+                scenario.kind,
+                scenario.container_width
+            ),
+            None
+        );
+        assert_eq!(restored.num_items(), 0);
+
+        assert_eq!(
+            restore(
+                &mut restored,
+                &snapshot,
+                scenario.kind,
+                scenario.container_width
+            ),
+            Some(height)
+        );
+        assert_eq!(items(&restored), items(&layout));
+        assert_eq!(headers(&restored), headers(&layout));
+        assert_eq!(restored.snapshot(), Some(snapshot));
+        for (index, &(width, height)) in scenario.dimensions.iter().enumerate() {
+            restored.set_dimension(index, width, height);
+        }
+        assert_eq!(
+            compute_all(&mut restored, scenario.kind, scenario.container_width),
+            height
+        );
+        assert_eq!(items(&restored), items(&layout));
+
+        let index = rng.index(scenario.dimensions.len());
+        let dimension = (rng.range_of(1, 6000), rng.range_of(1, 6000));
+        layout.set_dimension(index, dimension.0, dimension.1);
+        restored.set_dimension(index, dimension.0, dimension.1);
+        assert_eq!(
+            compute_all(&mut restored, scenario.kind, scenario.container_width),
+            compute_all(&mut layout, scenario.kind, scenario.container_width)
+        );
+        assert_eq!(items(&restored), items(&layout));
+        assert_eq!(headers(&restored), headers(&layout));
+    }
+}
+
+#[test]
+fn corrupted_snapshots_are_not_restored() {
+    let kind = MasonryType::Justified;
+    let mut layout = Layout::new(10, 100, 8);
+    for index in 0..10 {
+        layout.set_dimension(index, [300, 100, 200][index % 3], 200);
+    }
+    layout.add_group(0, 20);
+    layout.add_group(5, 20);
+    compute_all(&mut layout, kind, 500);
+    let snapshot = layout.snapshot().unwrap();
+
+    // Key, height and number of items, 10 items, number of groups, 2 groups and no positions,
+    // followed by the number of row breaks and 11 row breaks.
+    let items = layout.snapshot_key(kind, 500).len() + 8;
+    let groups = items + 10 * 22 + 4;
+    let row_breaks = groups + 2 * 22 + 4 + 4;
+    assert_eq!(snapshot.len(), row_breaks + 11 * 8);
+    let corruptions: [(usize, u32, usize); 7] = [
+        // Aspect ratio without a width or wider than the scale
+        (items, 0, 2),
+        (items, 10_001, 2),
+        // Span without columns
+        (items + 4, 0, 1),
+        // Group starts in descending order or after the last item
+        (groups, 7, 4),
+        (groups + 22, 11, 4),
+        // Row that ends at the last item and starts after it or at the end of the previous group
+        (row_breaks + 10 * 8 + 4, 6, 4),
+        (row_breaks + 5 * 8 + 4, 5, 4),
+    ];
+    for (offset, value, len) in corruptions {
+        let mut corrupted = snapshot.clone();
+        corrupted[offset..offset + len].copy_from_slice(&value.to_le_bytes()[..len]);
+        let mut restored = Layout::new(0, 100, 8);
+        assert_eq!(
+            restore(&mut restored, &corrupted, kind, 500),
+            None,
+            "{offset}"
+        );
+        assert_eq!(restored.num_items(), 0);
+    }
+
+    let mut restored = Layout::new(0, 100, 8);
+    assert!(restore(&mut restored, &snapshot, kind, 500).is_some());
+    for index in 0..10 {
+        restored.set_dimension(index, 100, 100);
+        layout.set_dimension(index, 100, 100);
+    }
+    assert_eq!(
+        compute_all(&mut restored, kind, 500),
+        compute_all(&mut layout, kind, 500)
+    );
+}
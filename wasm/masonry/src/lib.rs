@@ -1,5 +1,5 @@
 #![deny(clippy::pedantic)]
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(stdsimd)]
 #![feature(atomic_mut_ptr)]
 extern crate alloc;
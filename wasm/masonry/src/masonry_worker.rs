@@ -1,52 +1,265 @@
-use crate::data::{Computation, MasonryConfig, MasonryType};
+use crate::data::{
+    ColumnMode, Command, Computation, Direction, LastRow, MasonryConfig, MasonryType,
+    NavigationDirection,
+};
 use crate::layout::{Layout, Transform};
-use crate::sync::{receive_output, send_computation};
+#[cfg(target_arch = "wasm32")]
+use crate::sync::completion;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::sync::wait_for_completion;
+use crate::sync::{
+    close_channel, open_channel, receive_output, send_computation, LayoutGuard, SharedLayout,
+};
+use crate::util::UnwrapOrAbort;
 
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::AtomicI32;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct MasonryWorker {
-    layout: Layout,
+    handle: u32,
+    layout: Arc<SharedLayout>,
+    /// Changes that could not be applied yet because a computation was running.
+    commands: Vec<Command>,
+    config: MasonryConfig,
+    num_items: usize,
+    dimensions: Vec<u16>,
+    /// Buffer for the transforms returned by [`MasonryWorker::interpolate()`].
+    interpolated: Vec<Transform>,
+    items_in_range: [u32; 2],
+    /// Buffer for the indices returned by [`MasonryWorker::items_in_rect()`].
+    selection: Vec<u32>,
 }
 
 #[wasm_bindgen]
 impl MasonryWorker {
     #[wasm_bindgen(constructor)]
-    /// Creates a new worker from a worker that was initialized with the `worker.js` script.
-    pub fn new(num_items: usize) -> MasonryWorker {
-        MasonryWorker {
-            layout: Layout::new(
-                num_items,
+    /// Creates a new worker with its own channel to a web worker.
+    ///
+    /// The handle returned by [`MasonryWorker::get_handle()`] has to be sent to a web worker that
+    /// was initialized with the `worker.js` script, which then computes the layouts of this
+    /// instance. Throws an error if too many instances exist at the same time.
+    pub fn new(num_items: usize) -> Result<MasonryWorker, JsValue> {
+        let handle = open_channel()
+            .ok_or_else(|| JsValue::from_str("Too many masonry workers are in use."))?;
+        Ok(MasonryWorker {
+            handle,
+            layout: Arc::new(SharedLayout::new(
+                handle,
+                Layout::new(
+                    num_items,
+                    MasonryConfig::DEFAULT_THUMBNAIL_SIZE,
+                    MasonryConfig::DEFAULT_PADDING,
+                ),
+            )),
+            commands: Vec::new(),
+            config: MasonryConfig::new(
+                MasonryType::Vertical,
                 MasonryConfig::DEFAULT_THUMBNAIL_SIZE,
                 MasonryConfig::DEFAULT_PADDING,
             ),
-        }
+            num_items,
+            dimensions: Vec::new(),
+            interpolated: Vec::new(),
+            items_in_range: [0; 2],
+            selection: Vec::new(),
+        })
+    }
+
+    /// Returns the handle of the channel that has to be passed to `run` in the web worker.
+    pub fn get_handle(&self) -> u32 {
+        self.handle
     }
 
     /// Computes the transforms of all items.
     ///
-    /// # Safety
+    /// If only dimensions were changed or items were added or removed since the previous
+    /// computation with the same arguments, the layout is only computed again from the first
+    /// affected row or column onwards.
     ///
-    /// The returned `Promise` must be `await`ed. Calls to any other method of [`MasonryWorker`]
-    /// while the `Promise` is still pending will lead to undefined behaviour. As long as the value
-    /// is `await`ed you can enjoy lock free concurrency.
+    /// The returned `Promise` resolves to `"ok"` once the computation is finished. Calling this
+    /// method again while the `Promise` is still pending cancels the pending computation at the
+    /// next row or column and its `Promise` resolves to `"superseded"` right away. Only the most
+    /// recent computation is finished, no matter how often this method was called in between.
+    ///
+    /// While a computation is running, the layout belongs to the worker thread. Changes to the
+    /// items and groups are queued until the computation is finished or are applied by the next
+    /// computation. Reading transforms throws an error until the `Promise` of the most recent
+    /// computation is resolved, even while the worker thread switches between two computations.
+    #[cfg(target_arch = "wasm32")]
     pub fn compute(
         &mut self,
-        width: u16,
+        width: u32,
         kind: MasonryType,
-        thumbnail_size: u16,
+        thumbnail_size: u32,
         padding: u16,
     ) -> js_sys::Promise {
-        send_computation(Computation::new(
-            width,
-            MasonryConfig::new(kind, thumbnail_size, padding),
-            &mut self.layout,
-        ))
+        completion(self.send(width, kind, thumbnail_size, padding))
+    }
+
+    /// Serializes the most recent finished computation into a compact binary snapshot, for example
+    /// to store it and show the layout immediately on the next start with
+    /// [`MasonryWorker::restore()`].
+    ///
+    /// Returns `undefined` if items or settings were changed since the computation. Throws an
+    /// error if a computation is running.
+    pub fn snapshot(&mut self) -> Result<Option<Vec<u8>>, JsValue> {
+        Ok(self.try_lock()?.snapshot())
+    }
+
+    /// Restores the items, groups and transforms of a snapshot from [`MasonryWorker::snapshot()`]
+    /// instead of computing the layout, with the same arguments as [`MasonryWorker::compute()`].
+    ///
+    /// Returns the height of the container or `undefined` if the snapshot was taken with a
+    /// different kind, width or other settings or is malformed, in which case nothing is changed
+    /// and the layout has to be computed. [`MasonryWorker::get_height()`] is not updated by a
+    /// restore. Setting the same dimensions afterwards does not change the layout, so the next
+    /// computation with the same arguments finishes without computing anything. Throws an error if
+    /// a computation is running.
+    pub fn restore(
+        &mut self,
+        snapshot: &[u8],
+        width: u32,
+        kind: MasonryType,
+        thumbnail_size: u32,
+        padding: u16,
+    ) -> Result<Option<u32>, JsValue> {
+        let mut config = self.config;
+        config.kind = kind;
+        config.thumbnail_size = thumbnail_size;
+        config.padding = padding;
+        let (height, num_items) = {
+            let mut layout = self.try_lock()?;
+            // The snapshot is read with the new settings by a layout without items, so that the
+            // settings of the layout are only changed if it is restored.
+            let mut settings = Layout::new(0, thumbnail_size, padding);
+            config.apply(&mut settings);
+            match settings.read_snapshot(snapshot, kind, width) {
+                Some(snapshot) => {
+                    config.apply(&mut layout);
+                    (layout.apply_snapshot(snapshot), layout.num_items())
+                }
+                None => return Ok(None),
+            }
+        };
+        self.config = config;
+        self.num_items = num_items;
+        Ok(Some(height))
+    }
+
+    /// Sets how much the height of a row may deviate from the thumbnail size in percent for the
+    /// [`MasonryType::Justified`] layout.
+    ///
+    /// The thumbnail size is the target height of all rows. A higher deviation results in fewer
+    /// cropped looking rows but also in more uneven row heights. It also limits how much the
+    /// previous row may be stretched with [`LastRow::Balance`]. The default is 25%.
+    pub fn set_max_row_deviation(&mut self, percent: u8) {
+        self.config.max_row_deviation = percent;
+    }
+
+    /// Sets how the last row is aligned if it does not fill the container width for the
+    /// [`MasonryType::Horizontal`] and [`MasonryType::Justified`] layouts.
+    ///
+    /// In grouped layouts this applies to the last row of every group. The default is
+    /// [`LastRow::Keep`].
+    pub fn set_last_row(&mut self, last_row: LastRow) {
+        self.config.last_row = last_row;
+    }
+
+    /// Sets how many times as wide as high or vice versa an item can be at most.
+    ///
+    /// Items with more extreme aspect ratios are cropped, so that very narrow or wide images do
+    /// not take up a whole row or column. Passing 0 disables cropping, e.g. for panoramas. The
+    /// default is 3.
+    pub fn set_max_aspect_ratio(&mut self, max_aspect_ratio: f32) {
+        self.config.max_aspect_ratio = max_aspect_ratio;
+    }
+
+    /// Sets the direction in which the items are placed for all layouts.
+    ///
+    /// With [`Direction::TopToBottom`] the layout is transposed for horizontally scrolling
+    /// containers like a filmstrip. The width passed to [`MasonryWorker::compute()`] is then the
+    /// height of the container and [`MasonryWorker::get_height()`] returns its width. The default
+    /// is [`Direction::LeftToRight`].
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.config.direction = direction;
+    }
+
+    /// Sets the gap between the items next to each other for all layouts. Passing `undefined` uses
+    /// the padding passed to [`MasonryWorker::compute()`], which is the default.
+    pub fn set_column_gap(&mut self, column_gap: Option<u16>) {
+        self.config.column_gap = column_gap;
+    }
+
+    /// Sets the gap between the rows and groups for all layouts. Passing `undefined` uses the
+    /// padding passed to [`MasonryWorker::compute()`], which is the default.
+    pub fn set_row_gap(&mut self, row_gap: Option<u16>) {
+        self.config.row_gap = row_gap;
+    }
+
+    /// Sets the space between the container edges and the items for all layouts.
+    ///
+    /// The inset is part of the width passed to [`MasonryWorker::compute()`] and of the height
+    /// returned by [`MasonryWorker::get_height()`]. The default is 0.
+    pub fn set_inset(&mut self, inset: u16) {
+        self.config.inset = inset;
+    }
+
+    /// Sets the size of the grid to which the positions set with
+    /// [`MasonryWorker::set_position()`] are rounded in the [`MasonryType::Freeform`] layout. The
+    /// default is 0, which disables snapping.
+    pub fn set_snap(&mut self, snap: u16) {
+        self.config.snap = snap;
     }
 
-    /// Returns height of the container from the most recent computation.
+    /// Sets the height of the space below the image of every item for a caption, for example the
+    /// file name, in all layouts.
+    ///
+    /// The space is added to the height of every [`Transform`] without distorting the aspect ratio
+    /// of the image, which ends the caption height above the bottom of the transform. Items with a
+    /// position set with [`MasonryWorker::set_position()`] keep their height. With
+    /// [`Direction::TopToBottom`] the space is added to the width instead and the caption is right
+    /// of the image. The default is 0.
+    pub fn set_caption_height(&mut self, caption_height: u16) {
+        self.config.caption_height = caption_height;
+    }
+
+    /// Sets how the columns of the vertical masonry and [`MasonryType::Grid`] layouts are chosen.
+    ///
+    /// The meaning of the arguments depends on the mode:
+    /// - [`ColumnMode::Auto`]: Both are ignored and the thumbnail size is the column width.
+    /// - [`ColumnMode::Fixed`] and [`ColumnMode::Fit`]: `first` is the number of columns.
+    /// - [`ColumnMode::Width`]: `first` is the minimum and `second` the maximum column width
+    ///   without the gap. A maximum of 0 does not limit the width.
+    ///
+    /// The default is [`ColumnMode::Auto`].
+    pub fn set_column_mode(&mut self, mode: ColumnMode, first: u32, second: u32) {
+        self.config.column_mode = (mode, first, second);
+    }
+
+    /// Sets whether the transforms from before a computation are kept, so that changes of the
+    /// layout can be animated with [`MasonryWorker::get_previous_transform()`] or
+    /// [`MasonryWorker::interpolate()`]. The default is false.
+    pub fn set_retain_previous(&mut self, retain_previous: bool) {
+        self.config.retain_previous = retain_previous;
+    }
+
+    /// Returns height of the container from the most recent finished computation.
+    ///
+    /// The height ends at the bottom of the last item or header plus the inset, without a gap
+    /// below them.
     pub fn get_height(&self) -> u32 {
-        receive_output()
+        receive_output(self.handle)
+    }
+
+    /// Returns the scroll position that keeps the item passed to [`MasonryWorker::set_anchor()`] in
+    /// place after the most recent finished computation.
+    ///
+    /// Returns `undefined` if no anchor was set before the computation or the item does not exist.
+    /// Throws an error if a computation is running.
+    pub fn get_scroll_position(&mut self) -> Result<Option<u32>, JsValue> {
+        Ok(self.try_lock()?.get_scroll_position())
     }
 
     /// Set the number of items that need to be computed.
@@ -56,7 +269,8 @@ impl MasonryWorker {
     /// a lot. Allocations can be vary in performance depending on the provided allocator. This
     /// makes no efforts and uses the standard library allocator.
     pub fn resize(&mut self, new_len: usize) {
-        self.layout.resize(new_len);
+        self.num_items = new_len;
+        self.apply(Command::Resize(new_len));
     }
 
     /// Set the dimension of one item at the given index if it is smaller than the item count.
@@ -64,22 +278,321 @@ impl MasonryWorker {
     /// You have to set the dimensions of the items if you want to compute a vertical or horizontal
     /// masonry layout. For grid layout this is not necessary.
     pub fn set_dimension(&mut self, index: usize, src_width: u16, src_height: u16) {
-        self.layout.set_dimension(index, src_width, src_height);
+        self.apply(Command::SetDimension(index, src_width, src_height));
+    }
+
+    /// Sets the number of columns and rows the item at the given index covers if it is smaller
+    /// than the item count.
+    ///
+    /// Spans make it possible to highlight items in the [`MasonryType::Grid`] and vertical masonry
+    /// layouts. Smaller items after them fill the holes they leave. In the vertical masonry
+    /// layouts only the number of columns is used and groups with spans are always laid out like
+    /// [`MasonryType::Vertical`]. The default is one column and one row.
+    pub fn set_span(&mut self, index: usize, columns: u8, rows: u8) {
+        self.apply(Command::SetSpan(index, columns, rows));
+    }
+
+    /// Keeps the item at the given index in place during the next computation, for example when
+    /// the thumbnail size changes.
+    ///
+    /// The offset is the distance of the top of the item from the top of the viewport before the
+    /// computation, which is negative if the item starts above the viewport. Afterwards
+    /// [`MasonryWorker::get_scroll_position()`] returns the scroll position at which the item is at
+    /// the same offset again. With [`Direction::TopToBottom`] the offset and the scroll position
+    /// are horizontal.
+    pub fn set_anchor(&mut self, index: usize, offset: i32) {
+        self.apply(Command::SetAnchor(index, offset));
+    }
+
+    /// Sets the width, height, top and left offset of the item at the given index in the
+    /// [`MasonryType::Freeform`] layout, for example after it was dragged.
+    ///
+    /// The offsets are relative to the top of the group below its header and do not include the
+    /// inset. With [`Direction::RightToLeft`] the left offset is mirrored and with
+    /// [`Direction::TopToBottom`] the position is transposed like the rest of the layout. The item
+    /// is moved into the container if it is outside and all items without a position are placed
+    /// around it.
+    pub fn set_position(&mut self, index: usize, width: u32, height: u32, top: u32, left: u32) {
+        let position = Some([width, height, top, left]);
+        self.apply(Command::SetPosition(index, position));
+    }
+
+    /// Removes the position of the item at the given index, so that the
+    /// [`MasonryType::Freeform`] layout places it automatically again.
+    pub fn clear_position(&mut self, index: usize) {
+        self.apply(Command::SetPosition(index, None));
+    }
+
+    /// Sets the dimensions of the items from the first item onwards.
+    ///
+    /// The array contains the width and height of every item after each other. Setting all
+    /// dimensions at once is a lot faster than calling [`MasonryWorker::set_dimension()`] for every
+    /// item because it crosses the boundary between JavaScript and WebAssembly only once.
+    pub fn set_dimensions(&mut self, dimensions: &[u16]) {
+        match self.layout.try_lock(&mut self.commands) {
+            Some(mut layout) => layout.set_dimensions(dimensions),
+            None => self
+                .commands
+                .push(Command::SetDimensions(dimensions.to_vec())),
+        }
+    }
+
+    /// Returns a pointer to a buffer with room for the width and height of every item.
+    ///
+    /// The width and height of every item are written after each other into the buffer. Afterwards
+    /// [`MasonryWorker::flush_dimensions()`] has to be called to apply them. Unlike
+    /// [`MasonryWorker::set_dimensions()`] the array does not need to be copied into the
+    /// WebAssembly.Memory.
+    ///
+    /// # Safety
+    ///
+    /// The pointer is only valid until the next call to any method of [`MasonryWorker`] because
+    /// the WebAssembly.Memory may grow and the buffer may be moved. Always request a new pointer
+    /// before writing to the buffer.
+    pub fn dimensions_ptr(&mut self) -> *mut u16 {
+        self.dimensions.resize(2 * self.num_items, 0);
+        self.dimensions.as_mut_ptr()
+    }
+
+    /// Applies the dimensions written into the buffer returned by
+    /// [`MasonryWorker::dimensions_ptr()`].
+    pub fn flush_dimensions(&mut self) {
+        match self.layout.try_lock(&mut self.commands) {
+            Some(mut layout) => layout.set_dimensions(&self.dimensions),
+            None => self
+                .commands
+                .push(Command::SetDimensions(self.dimensions.clone())),
+        }
+    }
+
+    /// Starts a new group with a header at the given item index.
+    ///
+    /// A group contains all items up to the start of the next group and is laid out independently
+    /// from the other groups. The header is placed above the items of the group and spans the
+    /// whole container width. Groups must be added in ascending order of their start index. Items
+    /// in front of the first group are laid out without a header.
+    pub fn add_group(&mut self, start: usize, header_height: u16) {
+        self.apply(Command::AddGroup(start, header_height));
+    }
+
+    /// Removes all groups, so that all items are laid out as one group without a header.
+    pub fn clear_groups(&mut self) {
+        self.apply(Command::ClearGroups);
+    }
+
+    /// Returns a pointer to the transform of the header of the group at the given index.
+    ///
+    /// Throws an error if a computation is running.
+    ///
+    /// # Safety
+    ///
+    /// If the index is greater than or equal to the number of groups, it will return a null
+    /// pointer. Reading the WebAssembly.Memory will only return garbage. The pointer is only valid
+    /// until the next call to [`MasonryWorker::compute()`].
+    pub fn get_header_transform(&mut self, index: usize) -> Result<*const Transform, JsValue> {
+        let layout = self.try_lock()?;
+        Ok(match layout.get_header_transform(index) {
+            Some(transform) => transform,
+            None => core::ptr::null(),
+        })
     }
 
     /// Returns a pointer to the transform of the item at the given index.
     ///
-    /// The [`Transform`] object can be used to set the absolute position of an element.
+    /// The [`Transform`] object can be used to set the absolute position of an element. Throws an
+    /// error if a computation is running.
     ///
     /// # Safety
     ///
     /// If the index is greater than any number passed to [`MasonryWorker::resize()`], it will
-    /// return a null pointer. Reading the WebAssembly.Memory will only return garbage.
-    pub fn get_transform(&self, index: usize) -> *const Transform {
+    /// return a null pointer. Reading the WebAssembly.Memory will only return garbage. The pointer
+    /// is only valid until the next call to [`MasonryWorker::compute()`].
+    pub fn get_transform(&mut self, index: usize) -> Result<*const Transform, JsValue> {
+        let layout = self.try_lock()?;
         // This match will be optimized away because Option<&T> implements null pointer optimization.
-        match self.layout.get_transform(index) {
+        Ok(match layout.get_transform(index) {
+            Some(transform) => transform,
+            None => core::ptr::null(),
+        })
+    }
+
+    /// Returns a pointer to the transform of the item at the given index before the most recent
+    /// computation.
+    ///
+    /// Throws an error if a computation is running.
+    ///
+    /// # Safety
+    ///
+    /// If the item did not exist before the most recent computation or previous transforms are not
+    /// retained (see [`MasonryWorker::set_retain_previous()`]), it will return a null pointer. The
+    /// pointer is only valid until the next call to [`MasonryWorker::compute()`].
+    pub fn get_previous_transform(&mut self, index: usize) -> Result<*const Transform, JsValue> {
+        let layout = self.try_lock()?;
+        Ok(match layout.get_previous_transform(index) {
             Some(transform) => transform,
             None => core::ptr::null(),
+        })
+    }
+
+    /// Returns a pointer to the transforms of the items from start to end (exclusive) interpolated
+    /// between their previous and current transforms.
+    ///
+    /// `t` is the progress of the animation from 0 (previous) to 1 (current). Items that did not
+    /// exist before the most recent computation stay at their current transform. Throws an error
+    /// if a computation is running.
+    ///
+    /// # Safety
+    ///
+    /// The pointer can be read as an array of [`Transform`] objects, which is shorter than the
+    /// range if it exceeds the number of items. It always points to the same buffer, which is
+    /// overwritten by the next call to this method.
+    pub fn interpolate(
+        &mut self,
+        start: usize,
+        end: usize,
+        t: f32,
+    ) -> Result<*const Transform, JsValue> {
+        let layout = MasonryWorker::lock(&self.layout, &mut self.commands)?;
+        self.interpolated.clear();
+        self.interpolated
+            .extend((start..end).map_while(|index| layout.interpolate(index, t)));
+        Ok(self.interpolated.as_ptr())
+    }
+
+    /// Returns a pointer to the start and end (exclusive) index of the items which are visible
+    /// between top and bottom in the most recent computation.
+    ///
+    /// The range is the smallest range that contains every visible item and works for every
+    /// [`MasonryType`]. In the vertical masonry layout it can contain items that are not visible
+    /// because the items of the columns are not sorted by their top offset. If no item is visible
+    /// the range is empty.
+    ///
+    /// The pointer can be read as an array of two u32 and always points to the same location. Its
+    /// values are overwritten by the next call to this method. Throws an error if a computation is
+    /// running.
+    pub fn items_in_range(&mut self, top: u32, bottom: u32) -> Result<*const u32, JsValue> {
+        let items = self.try_lock()?.items_in_range(top, bottom);
+        self.items_in_range =
+            [items.start, items.end].map(|index| u32::try_from(index).unwrap_or_abort());
+        Ok(self.items_in_range.as_ptr())
+    }
+
+    /// Returns the index of the item at x and y in the most recent computation, for example to find
+    /// the item under the pointer, or `undefined` if there is none.
+    ///
+    /// Throws an error if a computation is running.
+    pub fn item_at(&mut self, x: u32, y: u32) -> Result<Option<usize>, JsValue> {
+        Ok(self.try_lock()?.item_at(x, y))
+    }
+
+    /// Writes the indices of all items which intersect with the rectangle at x and y with the
+    /// given width and height in the most recent computation into a buffer and returns their
+    /// number.
+    ///
+    /// The indices are in ascending order and can be read with [`MasonryWorker::selection_ptr()`].
+    /// Unlike [`MasonryWorker::items_in_range()`] only the items inside the rectangle are returned,
+    /// for example for a rubber band selection. Throws an error if a computation is running.
+    pub fn items_in_rect(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<usize, JsValue> {
+        let layout = MasonryWorker::lock(&self.layout, &mut self.commands)?;
+        self.selection.clear();
+        self.selection.extend(
+            layout
+                .items_in_rect(x, y, width, height)
+                .map(|index| u32::try_from(index).unwrap_or_abort()),
+        );
+        Ok(self.selection.len())
+    }
+
+    /// Returns a pointer to the indices written by [`MasonryWorker::items_in_rect()`].
+    ///
+    /// # Safety
+    ///
+    /// The pointer can be read as an array of as many u32 as returned by
+    /// [`MasonryWorker::items_in_rect()`]. It is only valid until the next call to that method.
+    pub fn selection_ptr(&self) -> *const u32 {
+        self.selection.as_ptr()
+    }
+
+    /// Returns the index of the item next to the item at the given index in the direction on the
+    /// screen, for example to move the focus with the arrow keys.
+    ///
+    /// The neighbor is found by the transforms of the most recent computation, so it works for
+    /// every [`MasonryType`] and [`Direction`]. Returns `undefined` if there is no item in the
+    /// direction or the index is out of bounds. Throws an error if a computation is running.
+    pub fn neighbor(
+        &mut self,
+        index: usize,
+        direction: NavigationDirection,
+    ) -> Result<Option<usize>, JsValue> {
+        Ok(self.try_lock()?.neighbor(index, direction))
+    }
+}
+
+impl Drop for MasonryWorker {
+    /// Closes the channel, which makes the web worker return from `run`.
+    fn drop(&mut self) {
+        close_channel(self.handle);
+    }
+}
+
+impl MasonryWorker {
+    /// Same as [`MasonryWorker::compute()`], but blocks until the computation is finished and
+    /// returns whether it finished, which is only possible outside of the browser.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compute_blocking(
+        &mut self,
+        width: u32,
+        kind: MasonryType,
+        thumbnail_size: u32,
+        padding: u16,
+    ) -> bool {
+        wait_for_completion(&self.send(width, kind, thumbnail_size, padding))
+    }
+
+    /// Sends a computation with the queued commands to the worker thread and returns its status.
+    fn send(
+        &mut self,
+        width: u32,
+        kind: MasonryType,
+        thumbnail_size: u32,
+        padding: u16,
+    ) -> Arc<AtomicI32> {
+        self.config.kind = kind;
+        self.config.thumbnail_size = thumbnail_size;
+        self.config.padding = padding;
+        let commands = core::mem::take(&mut self.commands);
+        send_computation(
+            self.handle,
+            Computation::new(width, self.config, Arc::clone(&self.layout), commands),
+        )
+    }
+
+    /// Applies the command immediately or queues it if a computation is running.
+    fn apply(&mut self, command: Command) {
+        match self.layout.try_lock(&mut self.commands) {
+            Some(mut layout) => command.apply(&mut layout),
+            None => self.commands.push(command),
         }
     }
+
+    fn try_lock(&mut self) -> Result<LayoutGuard<'_>, JsValue> {
+        MasonryWorker::lock(&self.layout, &mut self.commands)
+    }
+
+    /// Same as [`MasonryWorker::try_lock()`], but only borrows the fields it needs, so that the
+    /// buffers of the worker can be written while the layout is locked.
+    fn lock<'a>(
+        layout: &'a SharedLayout,
+        commands: &'a mut Vec<Command>,
+    ) -> Result<LayoutGuard<'a>, JsValue> {
+        layout
+            .try_lock(commands)
+            .ok_or_else(|| JsValue::from_str("The layout is being computed."))
+    }
 }
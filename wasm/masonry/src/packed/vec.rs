@@ -1,4 +1,4 @@
-use core::ops::{AddAssign, Mul};
+use core::ops::{Add, AddAssign, Div, Mul};
 
 #[repr(transparent)]
 #[derive(Clone, Copy)]
@@ -125,6 +125,16 @@ impl From<U32x4> for F32x4 {
     }
 }
 
+impl Add for F32x4 {
+    type Output = F32x4;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let [a0, a1, a2, a3] = self.0;
+        let [b0, b1, b2, b3] = rhs.0;
+        F32x4([a0 + b0, a1 + b1, a2 + b2, a3 + b3])
+    }
+}
+
 impl Mul for F32x4 {
     type Output = F32x4;
 
@@ -134,3 +144,13 @@ impl Mul for F32x4 {
         F32x4([a0 * b0, a1 * b1, a2 * b2, a3 * b3])
     }
 }
+
+impl Div for F32x4 {
+    type Output = F32x4;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let [a0, a1, a2, a3] = self.0;
+        let [b0, b1, b2, b3] = rhs.0;
+        F32x4([a0 / b0, a1 / b1, a2 / b2, a3 / b3])
+    }
+}
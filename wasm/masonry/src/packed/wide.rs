@@ -1,10 +1,10 @@
 use core::{
     arch::wasm32::{
-        f32x4_convert_u32x4, f32x4_mul, f32x4_replace_lane, f32x4_splat, u32x4, u32x4_add,
-        u32x4_extract_lane, u32x4_lt, u32x4_max, u32x4_min, u32x4_replace_lane, u32x4_splat,
-        u32x4_trunc_sat_f32x4, v128, v128_bitselect,
+        f32x4_add, f32x4_convert_u32x4, f32x4_div, f32x4_mul, f32x4_replace_lane, f32x4_splat,
+        u32x4, u32x4_add, u32x4_extract_lane, u32x4_lt, u32x4_max, u32x4_min, u32x4_replace_lane,
+        u32x4_splat, u32x4_trunc_sat_f32x4, v128, v128_bitselect,
     },
-    ops::{AddAssign, Mul},
+    ops::{Add, AddAssign, Div, Mul},
     ptr,
 };
 
@@ -116,6 +116,15 @@ impl From<U32x4> for F32x4 {
     }
 }
 
+impl Add for F32x4 {
+    type Output = F32x4;
+
+    #[target_feature(enable = "simd128")]
+    fn add(self, rhs: Self) -> Self::Output {
+        F32x4(f32x4_add(self.0, rhs.0))
+    }
+}
+
 impl Mul for F32x4 {
     type Output = F32x4;
 
@@ -124,3 +133,12 @@ impl Mul for F32x4 {
         F32x4(f32x4_mul(self.0, rhs.0))
     }
 }
+
+impl Div for F32x4 {
+    type Output = F32x4;
+
+    #[target_feature(enable = "simd128")]
+    fn div(self, rhs: Self) -> Self::Output {
+        F32x4(f32x4_div(self.0, rhs.0))
+    }
+}
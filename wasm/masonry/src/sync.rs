@@ -3,88 +3,365 @@
 //! This whole module is akin to a channel (e.g. [`std::sync::mpsc::channel()`]). However, it uses
 //! statics to avoid sending a receiver to the web worker. As it stands now, there is no nice
 //! [`std::thread::spawn()`] abstraction and it probably won't be added any time.
+//!
+//! Every [`crate::masonry_worker::MasonryWorker`] opens its own channel in a fixed table of slots.
+//! The index of the slot is the handle that has to be passed to [`run()`] in its web worker.
 //! ```
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::{
-    cell::Cell,
-    sync::atomic::{AtomicI32, Ordering},
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicI32, AtomicPtr, AtomicU32, Ordering},
 };
 
 use wasm_bindgen::prelude::*;
 
-use crate::data::{Computation, MasonryType};
+use crate::data::{Command, Computation, MasonryType};
+use crate::layout::Layout;
+
+#[cfg(not(target_arch = "wasm32"))]
+extern crate std;
+
+/// Maximum number of channels that can be open at the same time.
+const MAX_CHANNELS: usize = 16;
 
-static MAIN_THREAD: AtomicI32 = AtomicI32::new(UNLOCKED);
-static WORKER_THREAD: AtomicI32 = AtomicI32::new(LOCKED);
-static INPUT: Data<Option<Computation>> = Data::new(None);
-static OUTPUT: Data<u32> = Data::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const FREE_CHANNEL: Channel = Channel::new();
+static CHANNELS: [Channel; MAX_CHANNELS] = [FREE_CHANNEL; MAX_CHANNELS];
 
 const LOCKED: i32 = 0;
 const UNLOCKED: i32 = 1;
 
+/// The slot is not used by any channel.
+const FREE: i32 = 0;
+/// The slot is used by a channel.
+const OPEN: i32 = 1;
+/// The channel was closed but its web worker has not exited yet.
+const CLOSING: i32 = 2;
+
+/// The computation is neither finished nor superseded yet.
+const PENDING: i32 = 0;
+/// The computation finished.
+const FINISHED: i32 = 1;
+/// A newer computation was sent or the channel was closed before the computation finished.
+const SUPERSEDED: i32 = 2;
+
+struct Channel {
+    state: AtomicI32,
+    worker_thread: AtomicI32,
+    input: AtomicPtr<Computation>,
+    output: AtomicU32,
+    /// Generation of the most recently sent computation.
+    generation: AtomicI32,
+    /// Generation of the most recently finished computation.
+    completed: AtomicI32,
+    /// Status of the most recently sent computation, which is superseded by the next one.
+    latest: AtomicPtr<AtomicI32>,
+}
+
 /// Function to be called in the web worker thread to compute the new layout.
 ///
+/// Returns once the channel with the `handle` is closed.
+///
 /// # Safety
 ///
 /// Do not import this function as it is already imported into the web worker thread (see
 /// `worker.js`).
 #[wasm_bindgen]
-pub fn run() {
+// wasm-bindgen only exports the function on wasm32.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub fn run(handle: u32) {
+    let channel = match CHANNELS.get(handle as usize) {
+        Some(channel) => channel,
+        None => return,
+    };
     loop {
-        atomic_wait32(&WORKER_THREAD, LOCKED, -1);
-        if let Some(computation) = INPUT.replace(None) {
-            OUTPUT.set(execute(computation));
+        atomic_wait32(&channel.worker_thread, LOCKED, -1);
+        // Put the worker thread back to sleep before taking the input, so that a computation sent
+        // in the meantime wakes it up again.
+        channel.worker_thread.store(LOCKED, Ordering::SeqCst);
+        if channel.state.load(Ordering::SeqCst) == CLOSING {
+            drop(channel.take_input());
+            channel.state.store(FREE, Ordering::SeqCst);
+            return;
+        }
+        while let Some(mut computation) = channel.take_input() {
+            let generation = computation.generation;
+            let is_superseded = || channel.generation.load(Ordering::SeqCst) != generation;
+            if let Some(height) = execute(&mut computation, &is_superseded) {
+                channel.output.store(height, Ordering::SeqCst);
+                channel.completed.store(generation, Ordering::SeqCst);
+                settle(&computation.status, FINISHED);
+            }
+        }
+    }
+}
+
+/// Opens a new channel and returns its handle or `None` if all slots are in use.
+pub fn open_channel() -> Option<u32> {
+    let (handle, channel) = (0_u32..).zip(&CHANNELS).find(|(_, channel)| {
+        channel
+            .state
+            .compare_exchange(FREE, OPEN, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    })?;
+    channel.worker_thread.store(LOCKED, Ordering::SeqCst);
+    channel.output.store(0, Ordering::SeqCst);
+    channel.generation.store(0, Ordering::SeqCst);
+    channel.completed.store(0, Ordering::SeqCst);
+    Some(handle)
+}
+
+/// Closes the channel and makes its web worker return from [`run()`].
+///
+/// Pending computations are cancelled and their `Promise`s resolve to `"superseded"`. The slot
+/// can only be reused once the web worker has returned.
+pub fn close_channel(handle: u32) {
+    let channel = &CHANNELS[handle as usize];
+    channel.state.store(CLOSING, Ordering::SeqCst);
+    channel.generation.fetch_add(1, Ordering::SeqCst);
+    channel.replace_latest(ptr::null_mut());
+    channel.worker_thread.store(UNLOCKED, Ordering::SeqCst);
+    atomic_notify(&channel.worker_thread, 1);
+}
+
+/// Wakes up the web worker thread of the channel, "sends" data to receiver and returns the status
+/// of the computation, which can be awaited with [`completion()`].
+///
+/// The previous computation is superseded right away unless it already finished. If the worker
+/// thread is still busy with it, it is cancelled as soon as possible. A previous computation that
+/// was not even started yet is dropped and its commands are applied by the new one instead.
+pub fn send_computation(handle: u32, mut computation: Computation) -> Arc<AtomicI32> {
+    let channel = &CHANNELS[handle as usize];
+    let generation = channel.generation.load(Ordering::SeqCst).wrapping_add(1);
+    computation.generation = generation;
+    let status = Arc::clone(&computation.status);
+    // Publish the generation first, so that the worker thread never considers the new computation
+    // to be superseded.
+    channel.generation.store(generation, Ordering::SeqCst);
+    channel.replace_latest(Arc::into_raw(Arc::clone(&status)) as *mut AtomicI32);
+    if let Some(previous) = channel.take_input() {
+        // The previous computation was not started yet, so its changes were never applied.
+        computation.commands.splice(0..0, previous.commands);
+    }
+    channel
+        .input
+        .store(Box::into_raw(Box::new(computation)), Ordering::SeqCst);
+    // Wake up the worker thread.
+    channel.worker_thread.store(UNLOCKED, Ordering::SeqCst);
+    atomic_notify(&channel.worker_thread, 1);
+    status
+}
+
+/// Returns a `Promise` that resolves to `"ok"` once the computation with the status is finished
+/// or to `"superseded"` if a newer computation was sent before.
+#[cfg(target_arch = "wasm32")]
+pub fn completion(status: Arc<AtomicI32>) -> js_sys::Promise {
+    use wasm_bindgen::JsCast;
+
+    #[wasm_bindgen]
+    extern "C" {
+        /// `Promise` whose `then` callback returns another `Promise`.
+        type Chain;
+
+        #[wasm_bindgen(method)]
+        fn then(this: &Chain, callback: &JsValue) -> js_sys::Promise;
+    }
+
+    match status.load(Ordering::SeqCst) {
+        PENDING => {
+            let changed = atomic_wait32_async(&status, PENDING);
+            let callback = Closure::once_into_js(move |_: JsValue| completion(status));
+            changed.unchecked_into::<Chain>().then(&callback)
         }
-        // Put the worker thread back to sleep and notify the main thread that work is finished.
-        WORKER_THREAD.store(LOCKED, Ordering::SeqCst);
-        MAIN_THREAD.store(UNLOCKED, Ordering::SeqCst);
-        atomic_notify(&MAIN_THREAD, 1);
+        FINISHED => js_sys::Promise::resolve(&JsValue::from_str("ok")),
+        _ => js_sys::Promise::resolve(&JsValue::from_str("superseded")),
     }
 }
 
-/// Wakes up the web worker thread and "sends" data to receiver.
-pub fn send_computation(computation: Computation) -> js_sys::Promise {
-    INPUT.set(Some(computation));
-    // Wake up the worker thread and make the main thread wait for the worker thread.
-    MAIN_THREAD.store(LOCKED, Ordering::SeqCst);
-    WORKER_THREAD.store(UNLOCKED, Ordering::SeqCst);
-    atomic_notify(&WORKER_THREAD, 1);
-    atomic_wait32_async(&MAIN_THREAD, LOCKED)
+/// Blocks until the computation with the status is finished or a newer computation was sent
+/// before and returns whether it finished.
+///
+/// Outside of the browser the calling thread is allowed to wait, so the worker thread can be an
+/// ordinary [`std::thread`] instead of a web worker.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn wait_for_completion(status: &AtomicI32) -> bool {
+    loop {
+        match status.load(Ordering::SeqCst) {
+            PENDING => atomic_wait32(status, PENDING, -1),
+            status => return status == FINISHED,
+        };
+    }
 }
 
-/// Returns the result of the most recent computation.
-pub fn receive_output() -> u32 {
-    OUTPUT.get()
+/// Sets the status of a pending computation and wakes up the threads waiting for it.
+fn settle(status: &AtomicI32, result: i32) {
+    if status
+        .compare_exchange(PENDING, result, Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok()
+    {
+        atomic_notify(status, u32::MAX);
+    }
 }
 
-fn execute(computation: Computation) -> u32 {
-    let (width, config, layout) = {
-        // SAFETY: Never use core::ptr::read. The returned value will be an owned value, which means
-        // its destructor will be run at the end of the function. This will lead to a double free.
-        // Instead we only get a mutable reference and have to depend on the user to `await` every
-        // `Promise` returned from `MasonryWorker::compute`.
-        match unsafe { computation.layout_ptr.as_mut() } {
-            Some(layout) => (computation.width, computation.config, layout),
-            None => return 0,
+/// Returns the result of the most recent computation of the channel.
+pub fn receive_output(handle: u32) -> u32 {
+    CHANNELS[handle as usize].output.load(Ordering::SeqCst)
+}
+
+impl Channel {
+    const fn new() -> Channel {
+        Channel {
+            state: AtomicI32::new(FREE),
+            worker_thread: AtomicI32::new(LOCKED),
+            input: AtomicPtr::new(ptr::null_mut()),
+            output: AtomicU32::new(0),
+            generation: AtomicI32::new(0),
+            completed: AtomicI32::new(0),
+            latest: AtomicPtr::new(ptr::null_mut()),
         }
-    };
-    layout.set_thumbnail_size(config.thumbnail_size);
-    layout.set_padding(config.padding);
+    }
+
+    /// Replaces the status of the most recently sent computation and supersedes the previous one
+    /// unless it already finished.
+    fn replace_latest(&self, status: *mut AtomicI32) {
+        let previous = self.latest.swap(status, Ordering::SeqCst);
+        if !previous.is_null() {
+            // SAFETY: Pointers in `latest` are always created with Arc::into_raw and the swap above
+            // took the reference from the channel.
+            let previous = unsafe { Arc::from_raw(previous) };
+            settle(&previous, SUPERSEDED);
+        }
+    }
+
+    fn take_input(&self) -> Option<Box<Computation>> {
+        let computation = self.input.swap(ptr::null_mut(), Ordering::SeqCst);
+        if computation.is_null() {
+            None
+        } else {
+            // SAFETY: Pointers in the input are always created with Box::into_raw and the swap
+            // above took the ownership from the other thread.
+            Some(unsafe { Box::from_raw(computation) })
+        }
+    }
+}
+
+fn execute(computation: &mut Computation, is_cancelled: &dyn Fn() -> bool) -> Option<u32> {
+    let (width, config) = (computation.width, computation.config);
+    let mut layout = computation.layout.lock();
+    for command in computation.commands.drain(..) {
+        command.apply(&mut layout);
+    }
+    config.apply(&mut layout);
 
     match config.kind {
-        MasonryType::Vertical => layout.compute_vertical(width),
-        MasonryType::Horizontal => layout.compute_horizontal(width),
-        MasonryType::Grid => layout.compute_grid(width),
+        MasonryType::Vertical => layout.compute_vertical(width, is_cancelled),
+        MasonryType::VerticalOrdered => layout.compute_vertical_ordered(width, is_cancelled),
+        MasonryType::VerticalBalanced => layout.compute_vertical_balanced(width, is_cancelled),
+        MasonryType::Horizontal => layout.compute_horizontal(width, is_cancelled),
+        MasonryType::Grid => layout.compute_grid(width, is_cancelled),
+        MasonryType::Justified => layout.compute_justified(width, is_cancelled),
+        MasonryType::Freeform => layout.compute_freeform(width, is_cancelled),
+    }
+}
+
+/// Layout that is shared between the main thread and the worker thread.
+///
+/// The worker thread locks the layout for the duration of a computation. The main thread never
+/// waits for the lock and has to queue its changes as [`Command`]s instead as long as any
+/// computation of the channel is pending, even in between two computations.
+pub struct SharedLayout {
+    /// Handle of the channel whose worker thread computes the layout.
+    handle: u32,
+    state: AtomicI32,
+    layout: UnsafeCell<Layout>,
+}
+
+/// Exclusive access to a [`SharedLayout`] until it is dropped.
+pub struct LayoutGuard<'a>(&'a SharedLayout);
+
+impl SharedLayout {
+    pub fn new(handle: u32, layout: Layout) -> SharedLayout {
+        SharedLayout {
+            handle,
+            state: AtomicI32::new(UNLOCKED),
+            layout: UnsafeCell::new(layout),
+        }
+    }
+
+    /// Locks the layout if no computation is pending and applies the queued commands to it first.
+    ///
+    /// The worker thread releases the lock between two computations, so the main thread must not
+    /// lock it before the most recent computation is finished. Otherwise it would apply newer
+    /// commands before the ones that were sent with the next computation.
+    pub fn try_lock(&self, pending: &mut Vec<Command>) -> Option<LayoutGuard<'_>> {
+        let channel = &CHANNELS[self.handle as usize];
+        if channel.completed.load(Ordering::SeqCst) != channel.generation.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+        let mut guard = LayoutGuard(self);
+        for command in pending.drain(..) {
+            command.apply(&mut guard);
+        }
+        Some(guard)
+    }
+
+    /// Locks the layout and waits for the main thread to release it if necessary.
+    ///
+    /// Must only be called in the worker thread because the main thread is not allowed to block.
+    fn lock(&self) -> LayoutGuard<'_> {
+        while self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            atomic_wait32(&self.state, LOCKED, -1);
+        }
+        LayoutGuard(self)
+    }
+}
+
+/// SAFETY: The layout is only accessed through a [`LayoutGuard`] and there is only ever one.
+unsafe impl Sync for SharedLayout {}
+
+impl Deref for LayoutGuard<'_> {
+    type Target = Layout;
+
+    fn deref(&self) -> &Layout {
+        // SAFETY: The guard has exclusive access as long as it exists.
+        unsafe { &*self.0.layout.get() }
     }
 }
 
+impl DerefMut for LayoutGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Layout {
+        // SAFETY: The guard has exclusive access as long as it exists.
+        unsafe { &mut *self.0.layout.get() }
+    }
+}
+
+impl Drop for LayoutGuard<'_> {
+    fn drop(&mut self) {
+        self.0.state.store(UNLOCKED, Ordering::SeqCst);
+        atomic_notify(&self.0.state, 1);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
 fn atomic_wait32(atomic: &AtomicI32, expression: i32, timeout_ns: i64) -> i32 {
     unsafe { core::arch::wasm32::memory_atomic_wait32(atomic.as_mut_ptr(), expression, timeout_ns) }
 }
 
+#[cfg(target_arch = "wasm32")]
 fn atomic_notify(atomic: &AtomicI32, waiters: u32) -> u32 {
     unsafe { core::arch::wasm32::memory_atomic_notify(atomic.as_mut_ptr(), waiters) }
 }
 
+#[cfg(target_arch = "wasm32")]
 fn atomic_wait32_async(atomic: &AtomicI32, expression: i32) -> js_sys::Promise {
     #[wasm_bindgen]
     extern "C" {
@@ -112,28 +389,24 @@ fn atomic_wait32_async(atomic: &AtomicI32, expression: i32) -> js_sys::Promise {
     }
 }
 
-/// Wrapper around `Cell` to make it possible to use in statics.
-struct Data<T>(Cell<T>);
+// Other targets have no wait and notify instructions, so the waiting thread yields until the value
+// changes. This makes it possible to run the worker with `std::thread` outside of the browser.
 
-impl<T> Data<T> {
-    const fn new(value: T) -> Data<T> {
-        Data(Cell::new(value))
+#[cfg(not(target_arch = "wasm32"))]
+fn atomic_wait32(atomic: &AtomicI32, expression: i32, _timeout_ns: i64) -> i32 {
+    if atomic.load(Ordering::SeqCst) != expression {
+        return 1;
     }
-
-    fn set(&self, value: T) {
-        self.0.set(value);
-    }
-
-    fn replace(&self, value: T) -> T {
-        self.0.replace(value)
+    while atomic.load(Ordering::SeqCst) == expression {
+        std::thread::yield_now();
     }
+    0
 }
 
-impl<T: Copy> Data<T> {
-    fn get(&self) -> T {
-        self.0.get()
-    }
+#[cfg(not(target_arch = "wasm32"))]
+fn atomic_notify(_atomic: &AtomicI32, _waiters: u32) -> u32 {
+    0
 }
 
-/// Static values need to be sync.
-unsafe impl<T> Sync for Data<T> {}
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests;
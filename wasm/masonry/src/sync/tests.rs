@@ -0,0 +1,125 @@
+//! Tests of the channel between a [`MasonryWorker`] and a worker thread running [`run()`].
+//!
+//! Outside of the browser the worker thread is an ordinary [`std::thread`] and the computations
+//! are awaited with [`MasonryWorker::compute_blocking()`].
+extern crate std;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+use std::thread;
+
+use super::{
+    close_channel, open_channel, run, send_computation, wait_for_completion, SharedLayout, PENDING,
+};
+use crate::data::{Computation, MasonryConfig, MasonryType};
+use crate::layout::{Layout, Transform};
+use crate::masonry_worker::MasonryWorker;
+
+/// Returns width, height, top and left of the transform, as JavaScript reads it.
+fn rect(transform: *const Transform) -> [u32; 4] {
+    assert!(!transform.is_null());
+    // SAFETY: The transform is not null and consists of four u32.
+    unsafe { *transform.cast::<[u32; 4]>() }
+}
+
+#[test]
+fn worker_thread_computes_the_sent_layouts() {
+    let mut worker = MasonryWorker::new(0).ok().unwrap();
+    let handle = worker.get_handle();
+    let worker_thread = thread::spawn(move || run(handle));
+
+    let mut expected = Layout::new(0, 0, 0);
+    for (len, kind) in [
+        (20, MasonryType::Vertical),
+        (35, MasonryType::Horizontal),
+        (10, MasonryType::Grid),
+    ] {
+        worker.resize(len);
+        expected.resize(len);
+        for (index, step) in (0..len).zip(0_u16..) {
+            let (width, height) = (100 + step * 37 % 400, 100 + step * 91 % 300);
+            worker.set_dimension(index, width, height);
+            expected.set_dimension(index, width, height);
+        }
+        assert!(worker.compute_blocking(1000, kind, 200, 8));
+
+        MasonryConfig::new(kind, 200, 8).apply(&mut expected);
+        let height = match kind {
+            MasonryType::Vertical => expected.compute_vertical(1000, &|| false),
+            MasonryType::Horizontal => expected.compute_horizontal(1000, &|| false),
+            _ => expected.compute_grid(1000, &|| false),
+        };
+        assert_eq!(Some(worker.get_height()), height);
+        for index in 0..len {
+            let transform = worker.get_transform(index).ok().unwrap();
+            let expected_transform = expected.get_transform(index).unwrap();
+            assert_eq!(rect(transform), rect(expected_transform));
+        }
+    }
+
+    // Dropping the worker closes the channel, which makes the worker thread return.
+    drop(worker);
+    worker_thread.join().unwrap();
+}
+
+#[test]
+fn snapshots_that_are_not_restored_do_not_change_the_worker() {
+    let mut worker = MasonryWorker::new(30).ok().unwrap();
+    let handle = worker.get_handle();
+    let worker_thread = thread::spawn(move || run(handle));
+
+    for (index, step) in (0..30).zip(0_u16..) {
+        worker.set_dimension(index, 100 + step * 37 % 400, 300);
+    }
+    assert!(worker.compute_blocking(1000, MasonryType::Horizontal, 200, 8));
+    let snapshot = worker.snapshot().ok().unwrap().unwrap();
+
+    let restore = |worker: &mut MasonryWorker, snapshot: &[u8], thumbnail_size: u32| {
+        let kind = MasonryType::Horizontal;
+        worker
+            .restore(snapshot, 1000, kind, thumbnail_size, 8)
+            .ok()
+            .unwrap()
+    };
+    assert_eq!(restore(&mut worker, &snapshot, 300), None);
+    assert_eq!(
+        restore(&mut worker, &snapshot[..snapshot.len() - 1], 200),
+        None
+    );
+    // The settings of the layout were not changed, so it is still the same as the snapshot.
+    assert_eq!(worker.snapshot().ok().unwrap(), Some(snapshot.clone()));
+    assert_eq!(
+        restore(&mut worker, &snapshot, 200),
+        Some(worker.get_height())
+    );
+
+    drop(worker);
+    worker_thread.join().unwrap();
+}
+
+#[test]
+fn superseded_computations_resolve_when_a_newer_one_is_sent() {
+    let handle = open_channel().unwrap();
+    let layout = Arc::new(SharedLayout::new(handle, Layout::new(10, 200, 8)));
+    let computation = || {
+        let config = MasonryConfig::new(MasonryType::Grid, 200, 8);
+        Computation::new(1000, config, Arc::clone(&layout), Vec::new())
+    };
+
+    // The worker thread is not running yet, so only sending a newer computation settles the first.
+    let first = send_computation(handle, computation());
+    assert_eq!(first.load(Ordering::SeqCst), PENDING);
+    let second = send_computation(handle, computation());
+    assert!(!wait_for_completion(&first));
+
+    let worker_thread = thread::spawn(move || run(handle));
+    assert!(wait_for_completion(&second));
+    // A finished computation stays finished when a newer one is sent afterwards.
+    let third = send_computation(handle, computation());
+    assert!(wait_for_completion(&second));
+    assert!(wait_for_completion(&third));
+
+    close_channel(handle);
+    worker_thread.join().unwrap();
+}
@@ -7,7 +7,7 @@ impl<T> UnwrapOrAbort<T> for Option<T> {
     fn unwrap_or_abort(self) -> T {
         match self {
             Some(v) => v,
-            None => core::arch::wasm32::unreachable(),
+            None => abort(),
         }
     }
 }
@@ -17,7 +17,20 @@ impl<T, E> UnwrapOrAbort<T> for Result<T, E> {
     fn unwrap_or_abort(self) -> T {
         match self {
             Ok(v) => v,
-            Err(_) => core::arch::wasm32::unreachable(),
+            Err(_) => abort(),
         }
     }
 }
+
+/// Traps without formatting a panic message, which keeps the compiled module small.
+#[cfg(target_arch = "wasm32")]
+#[inline]
+fn abort() -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+fn abort() -> ! {
+    unreachable!()
+}
@@ -12,11 +12,11 @@ import {
 
 let IS_SIMD_SUPPORTED = false;
 
-export function run() {
+export function run(handle) {
   if (IS_SIMD_SUPPORTED) {
-    simdRun();
+    simdRun(handle);
   } else {
-    scalarRun();
+    scalarRun(handle);
   }
 }
 
@@ -27,6 +27,56 @@ export const MasonryType = Object.freeze({
   1: 'Horizontal',
   Grid: 2,
   2: 'Grid',
+  Justified: 3,
+  3: 'Justified',
+  VerticalOrdered: 4,
+  4: 'VerticalOrdered',
+  VerticalBalanced: 5,
+  5: 'VerticalBalanced',
+  Freeform: 6,
+  6: 'Freeform',
+});
+
+export const LastRow = Object.freeze({
+  Keep: 0,
+  0: 'Keep',
+  Justify: 1,
+  1: 'Justify',
+  Center: 2,
+  2: 'Center',
+  Balance: 3,
+  3: 'Balance',
+});
+
+export const Direction = Object.freeze({
+  LeftToRight: 0,
+  0: 'LeftToRight',
+  RightToLeft: 1,
+  1: 'RightToLeft',
+  TopToBottom: 2,
+  2: 'TopToBottom',
+});
+
+export const ColumnMode = Object.freeze({
+  Auto: 0,
+  0: 'Auto',
+  Fixed: 1,
+  1: 'Fixed',
+  Fit: 2,
+  2: 'Fit',
+  Width: 3,
+  3: 'Width',
+});
+
+export const NavigationDirection = Object.freeze({
+  Up: 0,
+  0: 'Up',
+  Down: 1,
+  1: 'Down',
+  Left: 2,
+  2: 'Left',
+  Right: 3,
+  3: 'Right',
 });
 
 export class MasonryWorker {
@@ -40,6 +90,14 @@ export class MasonryWorker {
     }
   }
 
+  free() {
+    this.worker.free();
+  }
+
+  get_handle() {
+    return this.worker.get_handle();
+  }
+
   compute(width, kind, thumbnail_size, padding) {
     return this.worker.compute(width, kind, thumbnail_size, padding);
   }
@@ -59,6 +117,130 @@ export class MasonryWorker {
   get_transform(index) {
     return this.worker.get_transform(index);
   }
+
+  add_group(start, header_height) {
+    return this.worker.add_group(start, header_height);
+  }
+
+  clear_groups() {
+    return this.worker.clear_groups();
+  }
+
+  get_header_transform(index) {
+    return this.worker.get_header_transform(index);
+  }
+
+  set_max_row_deviation(percent) {
+    return this.worker.set_max_row_deviation(percent);
+  }
+
+  set_last_row(last_row) {
+    return this.worker.set_last_row(last_row);
+  }
+
+  items_in_range(top, bottom) {
+    return this.worker.items_in_range(top, bottom);
+  }
+
+  set_dimensions(dimensions) {
+    return this.worker.set_dimensions(dimensions);
+  }
+
+  dimensions_ptr() {
+    return this.worker.dimensions_ptr();
+  }
+
+  flush_dimensions() {
+    return this.worker.flush_dimensions();
+  }
+
+  set_direction(direction) {
+    return this.worker.set_direction(direction);
+  }
+
+  set_span(index, columns, rows) {
+    return this.worker.set_span(index, columns, rows);
+  }
+
+  set_max_aspect_ratio(max_aspect_ratio) {
+    return this.worker.set_max_aspect_ratio(max_aspect_ratio);
+  }
+
+  set_column_gap(column_gap) {
+    return this.worker.set_column_gap(column_gap);
+  }
+
+  set_row_gap(row_gap) {
+    return this.worker.set_row_gap(row_gap);
+  }
+
+  set_inset(inset) {
+    return this.worker.set_inset(inset);
+  }
+
+  set_column_mode(mode, first, second) {
+    return this.worker.set_column_mode(mode, first, second);
+  }
+
+  set_retain_previous(retain_previous) {
+    return this.worker.set_retain_previous(retain_previous);
+  }
+
+  get_previous_transform(index) {
+    return this.worker.get_previous_transform(index);
+  }
+
+  interpolate(start, end, t) {
+    return this.worker.interpolate(start, end, t);
+  }
+
+  neighbor(index, direction) {
+    return this.worker.neighbor(index, direction);
+  }
+
+  item_at(x, y) {
+    return this.worker.item_at(x, y);
+  }
+
+  items_in_rect(x, y, width, height) {
+    return this.worker.items_in_rect(x, y, width, height);
+  }
+
+  selection_ptr() {
+    return this.worker.selection_ptr();
+  }
+
+  set_anchor(index, offset) {
+    return this.worker.set_anchor(index, offset);
+  }
+
+  get_scroll_position() {
+    return this.worker.get_scroll_position();
+  }
+
+  set_position(index, width, height, top, left) {
+    return this.worker.set_position(index, width, height, top, left);
+  }
+
+  clear_position(index) {
+    return this.worker.clear_position(index);
+  }
+
+  set_snap(snap) {
+    return this.worker.set_snap(snap);
+  }
+
+  set_caption_height(caption_height) {
+    return this.worker.set_caption_height(caption_height);
+  }
+
+  snapshot() {
+    return this.worker.snapshot();
+  }
+
+  restore(snapshot, width, kind, thumbnail_size, padding) {
+    return this.worker.restore(snapshot, width, kind, thumbnail_size, padding);
+  }
 }
 
 async function init(maybe_memory) {
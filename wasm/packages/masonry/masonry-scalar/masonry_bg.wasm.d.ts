@@ -1,13 +1,50 @@
 /* tslint:disable */
 /* eslint-disable */
 export function __wbg_masonryworker_free(a: number): void;
-export function masonryworker_new(a: number): number;
+export function masonryworker_new(a: number, b: number): void;
+export function masonryworker_get_handle(a: number): number;
 export function masonryworker_compute(a: number, b: number, c: number, d: number, e: number): number;
+export function masonryworker_snapshot(a: number, b: number): void;
+export function masonryworker_restore(a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number): void;
+export function masonryworker_set_max_row_deviation(a: number, b: number): void;
+export function masonryworker_set_last_row(a: number, b: number): void;
+export function masonryworker_set_max_aspect_ratio(a: number, b: number): void;
+export function masonryworker_set_direction(a: number, b: number): void;
+export function masonryworker_set_column_gap(a: number, b: number): void;
+export function masonryworker_set_row_gap(a: number, b: number): void;
+export function masonryworker_set_inset(a: number, b: number): void;
+export function masonryworker_set_snap(a: number, b: number): void;
+export function masonryworker_set_caption_height(a: number, b: number): void;
+export function masonryworker_set_column_mode(a: number, b: number, c: number, d: number): void;
+export function masonryworker_set_retain_previous(a: number, b: number): void;
 export function masonryworker_get_height(a: number): number;
+export function masonryworker_get_scroll_position(a: number, b: number): void;
 export function masonryworker_resize(a: number, b: number): void;
 export function masonryworker_set_dimension(a: number, b: number, c: number, d: number): void;
-export function masonryworker_get_transform(a: number, b: number): number;
-export function run(): void;
+export function masonryworker_set_span(a: number, b: number, c: number, d: number): void;
+export function masonryworker_set_anchor(a: number, b: number, c: number): void;
+export function masonryworker_set_position(a: number, b: number, c: number, d: number, e: number, f: number): void;
+export function masonryworker_clear_position(a: number, b: number): void;
+export function masonryworker_set_dimensions(a: number, b: number, c: number): void;
+export function masonryworker_dimensions_ptr(a: number): number;
+export function masonryworker_flush_dimensions(a: number): void;
+export function masonryworker_add_group(a: number, b: number, c: number): void;
+export function masonryworker_clear_groups(a: number): void;
+export function masonryworker_get_header_transform(a: number, b: number, c: number): void;
+export function masonryworker_get_transform(a: number, b: number, c: number): void;
+export function masonryworker_get_previous_transform(a: number, b: number, c: number): void;
+export function masonryworker_interpolate(a: number, b: number, c: number, d: number, e: number): void;
+export function masonryworker_items_in_range(a: number, b: number, c: number, d: number): void;
+export function masonryworker_item_at(a: number, b: number, c: number, d: number): void;
+export function masonryworker_items_in_rect(a: number, b: number, c: number, d: number, e: number, f: number): void;
+export function masonryworker_selection_ptr(a: number): number;
+export function masonryworker_neighbor(a: number, b: number, c: number, d: number): void;
+export function run(a: number): void;
 export const memory: WebAssembly.Memory;
+export const __wbindgen_export_1: WebAssembly.Table;
+export function _dyn_core__ops__function__FnMut__A____Output___R_as_wasm_bindgen__closure__WasmClosure___describe__invoke__hb5f328039aad7528(a: number, b: number, c: number): number;
+export function __wbindgen_add_to_stack_pointer(a: number): number;
+export function __wbindgen_free(a: number, b: number): void;
+export function __wbindgen_malloc(a: number): number;
 export function __wbindgen_thread_destroy(): void;
 export function __wbindgen_start(): void;
@@ -3,36 +3,149 @@
 /**
 * Function to be called in the web worker thread to compute the new layout.
 *
+* Returns once the channel with the `handle` is closed.
+*
 * # Safety
 *
 * Do not import this function as it is already imported into the web worker thread (see
 * `worker.js`).
+* @param {number} handle
 */
-export function run(): void;
+export function run(handle: number): void;
 /**
 */
 export enum MasonryType {
   Vertical,
   Horizontal,
   Grid,
+  Justified,
+/**
+* Vertical masonry layout that places the items in reading order from left to right. A column
+* is only skipped if it is more than one column width taller than the shortest one.
+*/
+  VerticalOrdered,
+/**
+* Vertical masonry layout that places every row of as many items as there are columns
+* together, with the highest item in the shortest column.
+*/
+  VerticalBalanced,
+/**
+* Layout where the items keep the positions that were set for them and all other items are
+* placed at the lowest free spot at the thumbnail size, like a moodboard.
+*/
+  Freeform,
+}
+/**
+* Alignment of the last row of a horizontal or justified layout if it does not fill the container
+* width.
+*/
+export enum LastRow {
+/**
+* Keeps the items at the thumbnail size aligned to the left.
+*/
+  Keep,
+/**
+* Scales the items so that they fill the container width.
+*/
+  Justify,
+/**
+* Keeps the items at the thumbnail size in the center.
+*/
+  Center,
+/**
+* Moves items from the previous row into the last row, so that both rows have about the same
+* width.
+*/
+  Balance,
+}
+/**
+* Direction in which the items are placed.
+*/
+export enum Direction {
+/**
+* Rows are filled from the left to the right and the container scrolls vertically.
+*/
+  LeftToRight,
+/**
+* Rows are filled from the right to the left and the container scrolls vertically.
+*/
+  RightToLeft,
+/**
+* Columns are filled from the top to the bottom and the container scrolls horizontally.
+*
+* Every layout is transposed: the container width becomes the height of the container and
+* the returned height becomes its width.
+*/
+  TopToBottom,
+}
+/**
+* Direction on the screen in which the next item is searched when navigating with the keyboard.
+*/
+export enum NavigationDirection {
+  Up,
+  Down,
+  Left,
+  Right,
+}
+/**
+* How the number and width of the columns of a vertical or grid layout are chosen.
+*
+* The columns never exceed the container width. If they are narrower in total, the space after
+* the last column stays empty.
+*/
+export enum ColumnMode {
+/**
+* As many columns as fit at about the thumbnail size, which fill the container width.
+*/
+  Auto,
+/**
+* A fixed number of columns that are as wide as the thumbnail size, or narrower if the
+* container is too narrow.
+*/
+  Fixed,
+/**
+* A fixed number of columns that fill the container width.
+*/
+  Fit,
+/**
+* As many columns as fit at a minimum width, which are at most as wide as a maximum width.
+*/
+  Width,
 }
 /**
 */
 export class MasonryWorker {
   free(): void;
 /**
-* Creates a new worker from a worker that was initialized with the `worker.js` script.
+* Creates a new worker with its own channel to a web worker.
+*
+* The handle returned by [`MasonryWorker::get_handle()`] has to be sent to a web worker that
+* was initialized with the `worker.js` script, which then computes the layouts of this
+* instance. Throws an error if too many instances exist at the same time.
 * @param {number} num_items
 */
   constructor(num_items: number);
 /**
+* Returns the handle of the channel that has to be passed to `run` in the web worker.
+* @returns {number}
+*/
+  get_handle(): number;
+/**
 * Computes the transforms of all items.
 *
-* # Safety
+* If only dimensions were changed or items were added or removed since the previous
+* computation with the same arguments, the layout is only computed again from the first
+* affected row or column onwards.
+*
+* The returned `Promise` resolves to `"ok"` once the computation is finished. Calling this
+* method again while the `Promise` is still pending cancels the pending computation at the
+* next row or column and its `Promise` resolves to `"superseded"` right away. Only the most
+* recent computation is finished, no matter how often this method was called in between.
 *
-* The returned `Promise` must be `await`ed. Calls to any other method of [`MasonryWorker`]
-* while the `Promise` is still pending will lead to undefined behaviour. As long as the value
-* is `await`ed you can enjoy lock free concurrency.
+* While a computation is running, the layout belongs to the worker thread. Changes to the
+* items and groups are queued until the computation is finished or are applied by the next
+* computation. Reading transforms throws an error until the `Promise` of the most recent
+* computation is resolved, even while the worker thread switches between two computations.
 * @param {number} width
 * @param {number} kind
 * @param {number} thumbnail_size
@@ -41,11 +154,150 @@ export class MasonryWorker {
 */
   compute(width: number, kind: number, thumbnail_size: number, padding: number): Promise<any>;
 /**
-* Returns height of the container from the most recent computation.
+* Serializes the most recent finished computation into a compact binary snapshot, for example
+* to store it and show the layout immediately on the next start with
+* [`MasonryWorker::restore()`].
+*
+* Returns `undefined` if items or settings were changed since the computation. Throws an
+* error if a computation is running.
+* @returns {Uint8Array | undefined}
+*/
+  snapshot(): Uint8Array | undefined;
+/**
+* Restores the items, groups and transforms of a snapshot from [`MasonryWorker::snapshot()`]
+* instead of computing the layout, with the same arguments as [`MasonryWorker::compute()`].
+*
+* Returns the height of the container or `undefined` if the snapshot was taken with a
+* different kind, width or other settings or is malformed, in which case nothing is changed
+* and the layout has to be computed. [`MasonryWorker::get_height()`] is not updated by a
+* restore. Setting the same dimensions afterwards does not change the layout, so the next
+* computation with the same arguments finishes without computing anything. Throws an error if
+* a computation is running.
+* @param {Uint8Array} snapshot
+* @param {number} width
+* @param {number} kind
+* @param {number} thumbnail_size
+* @param {number} padding
+* @returns {number | undefined}
+*/
+  restore(snapshot: Uint8Array, width: number, kind: number, thumbnail_size: number, padding: number): number | undefined;
+/**
+* Sets how much the height of a row may deviate from the thumbnail size in percent for the
+* [`MasonryType::Justified`] layout.
+*
+* The thumbnail size is the target height of all rows. A higher deviation results in fewer
+* cropped looking rows but also in more uneven row heights. It also limits how much the
+* previous row may be stretched with [`LastRow::Balance`]. The default is 25%.
+* @param {number} percent
+*/
+  set_max_row_deviation(percent: number): void;
+/**
+* Sets how the last row is aligned if it does not fill the container width for the
+* [`MasonryType::Horizontal`] and [`MasonryType::Justified`] layouts.
+*
+* In grouped layouts this applies to the last row of every group. The default is
+* [`LastRow::Keep`].
+* @param {number} last_row
+*/
+  set_last_row(last_row: number): void;
+/**
+* Sets how many times as wide as high or vice versa an item can be at most.
+*
+* Items with more extreme aspect ratios are cropped, so that very narrow or wide images do
+* not take up a whole row or column. Passing 0 disables cropping, e.g. for panoramas. The
+* default is 3.
+* @param {number} max_aspect_ratio
+*/
+  set_max_aspect_ratio(max_aspect_ratio: number): void;
+/**
+* Sets the direction in which the items are placed for all layouts.
+*
+* With [`Direction::TopToBottom`] the layout is transposed for horizontally scrolling
+* containers like a filmstrip. The width passed to [`MasonryWorker::compute()`] is then the
+* height of the container and [`MasonryWorker::get_height()`] returns its width. The default
+* is [`Direction::LeftToRight`].
+* @param {number} direction
+*/
+  set_direction(direction: number): void;
+/**
+* Sets the gap between the items next to each other for all layouts. Passing `undefined` uses
+* the padding passed to [`MasonryWorker::compute()`], which is the default.
+* @param {number | undefined} column_gap
+*/
+  set_column_gap(column_gap?: number): void;
+/**
+* Sets the gap between the rows and groups for all layouts. Passing `undefined` uses the
+* padding passed to [`MasonryWorker::compute()`], which is the default.
+* @param {number | undefined} row_gap
+*/
+  set_row_gap(row_gap?: number): void;
+/**
+* Sets the space between the container edges and the items for all layouts.
+*
+* The inset is part of the width passed to [`MasonryWorker::compute()`] and of the height
+* returned by [`MasonryWorker::get_height()`]. The default is 0.
+* @param {number} inset
+*/
+  set_inset(inset: number): void;
+/**
+* Sets the size of the grid to which the positions set with
+* [`MasonryWorker::set_position()`] are rounded in the [`MasonryType::Freeform`] layout. The
+* default is 0, which disables snapping.
+* @param {number} snap
+*/
+  set_snap(snap: number): void;
+/**
+* Sets the height of the space below the image of every item for a caption, for example the
+* file name, in all layouts.
+*
+* The space is added to the height of every [`Transform`] without distorting the aspect ratio
+* of the image, which ends the caption height above the bottom of the transform. Items with a
+* position set with [`MasonryWorker::set_position()`] keep their height. With
+* [`Direction::TopToBottom`] the space is added to the width instead and the caption is right
+* of the image. The default is 0.
+* @param {number} caption_height
+*/
+  set_caption_height(caption_height: number): void;
+/**
+* Sets how the columns of the vertical masonry and [`MasonryType::Grid`] layouts are chosen.
+*
+* The meaning of the arguments depends on the mode:
+* - [`ColumnMode::Auto`]: Both are ignored and the thumbnail size is the column width.
+* - [`ColumnMode::Fixed`] and [`ColumnMode::Fit`]: `first` is the number of columns.
+* - [`ColumnMode::Width`]: `first` is the minimum and `second` the maximum column width
+*   without the gap. A maximum of 0 does not limit the width.
+*
+* The default is [`ColumnMode::Auto`].
+* @param {number} mode
+* @param {number} first
+* @param {number} second
+*/
+  set_column_mode(mode: number, first: number, second: number): void;
+/**
+* Sets whether the transforms from before a computation are kept, so that changes of the
+* layout can be animated with [`MasonryWorker::get_previous_transform()`] or
+* [`MasonryWorker::interpolate()`]. The default is false.
+* @param {boolean} retain_previous
+*/
+  set_retain_previous(retain_previous: boolean): void;
+/**
+* Returns height of the container from the most recent finished computation.
+*
+* The height ends at the bottom of the last item or header plus the inset, without a gap
+* below them.
 * @returns {number}
 */
   get_height(): number;
 /**
+* Returns the scroll position that keeps the item passed to [`MasonryWorker::set_anchor()`] in
+* place after the most recent finished computation.
+*
+* Returns `undefined` if no anchor was set before the computation or the item does not exist.
+* Throws an error if a computation is running.
+* @returns {number | undefined}
+*/
+  get_scroll_position(): number | undefined;
+/**
 * Set the number of items that need to be computed.
 *
 * Memory is never deallocated which means that even if the new len is smaller than the current
@@ -66,32 +318,276 @@ export class MasonryWorker {
 */
   set_dimension(index: number, src_width: number, src_height: number): void;
 /**
+* Sets the number of columns and rows the item at the given index covers if it is smaller
+* than the item count.
+*
+* Spans make it possible to highlight items in the [`MasonryType::Grid`] and vertical masonry
+* layouts. Smaller items after them fill the holes they leave. In the vertical masonry
+* layouts only the number of columns is used and groups with spans are always laid out like
+* [`MasonryType::Vertical`]. The default is one column and one row.
+* @param {number} index
+* @param {number} columns
+* @param {number} rows
+*/
+  set_span(index: number, columns: number, rows: number): void;
+/**
+* Keeps the item at the given index in place during the next computation, for example when
+* the thumbnail size changes.
+*
+* The offset is the distance of the top of the item from the top of the viewport before the
+* computation, which is negative if the item starts above the viewport. Afterwards
+* [`MasonryWorker::get_scroll_position()`] returns the scroll position at which the item is at
+* the same offset again. With [`Direction::TopToBottom`] the offset and the scroll position
+* are horizontal.
+* @param {number} index
+* @param {number} offset
+*/
+  set_anchor(index: number, offset: number): void;
+/**
+* Sets the width, height, top and left offset of the item at the given index in the
+* [`MasonryType::Freeform`] layout, for example after it was dragged.
+*
+* The offsets are relative to the top of the group below its header and do not include the
+* inset. With [`Direction::RightToLeft`] the left offset is mirrored and with
+* [`Direction::TopToBottom`] the position is transposed like the rest of the layout. The item
+* is moved into the container if it is outside and all items without a position are placed
+* around it.
+* @param {number} index
+* @param {number} width
+* @param {number} height
+* @param {number} top
+* @param {number} left
+*/
+  set_position(index: number, width: number, height: number, top: number, left: number): void;
+/**
+* Removes the position of the item at the given index, so that the
+* [`MasonryType::Freeform`] layout places it automatically again.
+* @param {number} index
+*/
+  clear_position(index: number): void;
+/**
+* Sets the dimensions of the items from the first item onwards.
+*
+* The array contains the width and height of every item after each other. Setting all
+* dimensions at once is a lot faster than calling [`MasonryWorker::set_dimension()`] for every
+* item because it crosses the boundary between JavaScript and WebAssembly only once.
+* @param {Uint16Array} dimensions
+*/
+  set_dimensions(dimensions: Uint16Array): void;
+/**
+* Returns a pointer to a buffer with room for the width and height of every item.
+*
+* The width and height of every item are written after each other into the buffer. Afterwards
+* [`MasonryWorker::flush_dimensions()`] has to be called to apply them. Unlike
+* [`MasonryWorker::set_dimensions()`] the array does not need to be copied into the
+* WebAssembly.Memory.
+*
+* # Safety
+*
+* The pointer is only valid until the next call to any method of [`MasonryWorker`] because
+* the WebAssembly.Memory may grow and the buffer may be moved. Always request a new pointer
+* before writing to the buffer.
+* @returns {number}
+*/
+  dimensions_ptr(): number;
+/**
+* Applies the dimensions written into the buffer returned by
+* [`MasonryWorker::dimensions_ptr()`].
+*/
+  flush_dimensions(): void;
+/**
+* Starts a new group with a header at the given item index.
+*
+* A group contains all items up to the start of the next group and is laid out independently
+* from the other groups. The header is placed above the items of the group and spans the
+* whole container width. Groups must be added in ascending order of their start index. Items
+* in front of the first group are laid out without a header.
+* @param {number} start
+* @param {number} header_height
+*/
+  add_group(start: number, header_height: number): void;
+/**
+* Removes all groups, so that all items are laid out as one group without a header.
+*/
+  clear_groups(): void;
+/**
+* Returns a pointer to the transform of the header of the group at the given index.
+*
+* Throws an error if a computation is running.
+*
+* # Safety
+*
+* If the index is greater than or equal to the number of groups, it will return a null
+* pointer. Reading the WebAssembly.Memory will only return garbage. The pointer is only valid
+* until the next call to [`MasonryWorker::compute()`].
+* @param {number} index
+* @returns {number}
+*/
+  get_header_transform(index: number): number;
+/**
 * Returns a pointer to the transform of the item at the given index.
 *
-* The [`Transform`] object can be used to set the absolute position of an element.
+* The [`Transform`] object can be used to set the absolute position of an element. Throws an
+* error if a computation is running.
 *
 * # Safety
 *
 * If the index is greater than any number passed to [`MasonryWorker::resize()`], it will
-* return a null pointer. Reading the WebAssembly.Memory will only return garbage.
+* return a null pointer. Reading the WebAssembly.Memory will only return garbage. The pointer
+* is only valid until the next call to [`MasonryWorker::compute()`].
 * @param {number} index
 * @returns {number}
 */
   get_transform(index: number): number;
+/**
+* Returns a pointer to the transform of the item at the given index before the most recent
+* computation.
+*
+* Throws an error if a computation is running.
+*
+* # Safety
+*
+* If the item did not exist before the most recent computation or previous transforms are not
+* retained (see [`MasonryWorker::set_retain_previous()`]), it will return a null pointer. The
+* pointer is only valid until the next call to [`MasonryWorker::compute()`].
+* @param {number} index
+* @returns {number}
+*/
+  get_previous_transform(index: number): number;
+/**
+* Returns a pointer to the transforms of the items from start to end (exclusive) interpolated
+* between their previous and current transforms.
+*
+* `t` is the progress of the animation from 0 (previous) to 1 (current). Items that did not
+* exist before the most recent computation stay at their current transform. Throws an error
+* if a computation is running.
+*
+* # Safety
+*
+* The pointer can be read as an array of [`Transform`] objects, which is shorter than the
+* range if it exceeds the number of items. It always points to the same buffer, which is
+* overwritten by the next call to this method.
+* @param {number} start
+* @param {number} end
+* @param {number} t
+* @returns {number}
+*/
+  interpolate(start: number, end: number, t: number): number;
+/**
+* Returns a pointer to the start and end (exclusive) index of the items which are visible
+* between top and bottom in the most recent computation.
+*
+* The range is the smallest range that contains every visible item and works for every
+* [`MasonryType`]. In the vertical masonry layout it can contain items that are not visible
+* because the items of the columns are not sorted by their top offset. If no item is visible
+* the range is empty.
+*
+* The pointer can be read as an array of two u32 and always points to the same location. Its
+* values are overwritten by the next call to this method. Throws an error if a computation is
+* running.
+* @param {number} top
+* @param {number} bottom
+* @returns {number}
+*/
+  items_in_range(top: number, bottom: number): number;
+/**
+* Returns the index of the item at x and y in the most recent computation, for example to find
+* the item under the pointer, or `undefined` if there is none.
+*
+* Throws an error if a computation is running.
+* @param {number} x
+* @param {number} y
+* @returns {number | undefined}
+*/
+  item_at(x: number, y: number): number | undefined;
+/**
+* Writes the indices of all items which intersect with the rectangle at x and y with the
+* given width and height in the most recent computation into a buffer and returns their
+* number.
+*
+* The indices are in ascending order and can be read with [`MasonryWorker::selection_ptr()`].
+* Unlike [`MasonryWorker::items_in_range()`] only the items inside the rectangle are returned,
+* for example for a rubber band selection. Throws an error if a computation is running.
+* @param {number} x
+* @param {number} y
+* @param {number} width
+* @param {number} height
+* @returns {number}
+*/
+  items_in_rect(x: number, y: number, width: number, height: number): number;
+/**
+* Returns a pointer to the indices written by [`MasonryWorker::items_in_rect()`].
+*
+* # Safety
+*
+* The pointer can be read as an array of as many u32 as returned by
+* [`MasonryWorker::items_in_rect()`]. It is only valid until the next call to that method.
+* @returns {number}
+*/
+  selection_ptr(): number;
+/**
+* Returns the index of the item next to the item at the given index in the direction on the
+* screen, for example to move the focus with the arrow keys.
+*
+* The neighbor is found by the transforms of the most recent computation, so it works for
+* every [`MasonryType`] and [`Direction`]. Returns `undefined` if there is no item in the
+* direction or the index is out of bounds. Throws an error if a computation is running.
+* @param {number} index
+* @param {number} direction
+* @returns {number | undefined}
+*/
+  neighbor(index: number, direction: number): number | undefined;
 }
 
 export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembly.Module;
 
 export interface InitOutput {
   readonly __wbg_masonryworker_free: (a: number) => void;
-  readonly masonryworker_new: (a: number) => number;
+  readonly masonryworker_new: (a: number, b: number) => void;
+  readonly masonryworker_get_handle: (a: number) => number;
   readonly masonryworker_compute: (a: number, b: number, c: number, d: number, e: number) => number;
+  readonly masonryworker_snapshot: (a: number, b: number) => void;
+  readonly masonryworker_restore: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number) => void;
+  readonly masonryworker_set_max_row_deviation: (a: number, b: number) => void;
+  readonly masonryworker_set_last_row: (a: number, b: number) => void;
+  readonly masonryworker_set_max_aspect_ratio: (a: number, b: number) => void;
+  readonly masonryworker_set_direction: (a: number, b: number) => void;
+  readonly masonryworker_set_column_gap: (a: number, b: number) => void;
+  readonly masonryworker_set_row_gap: (a: number, b: number) => void;
+  readonly masonryworker_set_inset: (a: number, b: number) => void;
+  readonly masonryworker_set_snap: (a: number, b: number) => void;
+  readonly masonryworker_set_caption_height: (a: number, b: number) => void;
+  readonly masonryworker_set_column_mode: (a: number, b: number, c: number, d: number) => void;
+  readonly masonryworker_set_retain_previous: (a: number, b: number) => void;
   readonly masonryworker_get_height: (a: number) => number;
+  readonly masonryworker_get_scroll_position: (a: number, b: number) => void;
   readonly masonryworker_resize: (a: number, b: number) => void;
   readonly masonryworker_set_dimension: (a: number, b: number, c: number, d: number) => void;
-  readonly masonryworker_get_transform: (a: number, b: number) => number;
-  readonly run: () => void;
+  readonly masonryworker_set_span: (a: number, b: number, c: number, d: number) => void;
+  readonly masonryworker_set_anchor: (a: number, b: number, c: number) => void;
+  readonly masonryworker_set_position: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+  readonly masonryworker_clear_position: (a: number, b: number) => void;
+  readonly masonryworker_set_dimensions: (a: number, b: number, c: number) => void;
+  readonly masonryworker_dimensions_ptr: (a: number) => number;
+  readonly masonryworker_flush_dimensions: (a: number) => void;
+  readonly masonryworker_add_group: (a: number, b: number, c: number) => void;
+  readonly masonryworker_clear_groups: (a: number) => void;
+  readonly masonryworker_get_header_transform: (a: number, b: number, c: number) => void;
+  readonly masonryworker_get_transform: (a: number, b: number, c: number) => void;
+  readonly masonryworker_get_previous_transform: (a: number, b: number, c: number) => void;
+  readonly masonryworker_interpolate: (a: number, b: number, c: number, d: number, e: number) => void;
+  readonly masonryworker_items_in_range: (a: number, b: number, c: number, d: number) => void;
+  readonly masonryworker_item_at: (a: number, b: number, c: number, d: number) => void;
+  readonly masonryworker_items_in_rect: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+  readonly masonryworker_selection_ptr: (a: number) => number;
+  readonly masonryworker_neighbor: (a: number, b: number, c: number, d: number) => void;
+  readonly run: (a: number) => void;
   readonly memory: WebAssembly.Memory;
+  readonly __wbindgen_export_1: WebAssembly.Table;
+  readonly _dyn_core__ops__function__FnMut__A____Output___R_as_wasm_bindgen__closure__WasmClosure___describe__invoke__hb5f328039aad7528: (a: number, b: number, c: number) => number;
+  readonly __wbindgen_add_to_stack_pointer: (a: number) => number;
+  readonly __wbindgen_free: (a: number, b: number) => void;
+  readonly __wbindgen_malloc: (a: number) => number;
   readonly __wbindgen_thread_destroy: () => void;
   readonly __wbindgen_start: () => void;
 }
@@ -45,21 +45,177 @@ function addHeapObject(obj) {
     heap[idx] = obj;
     return idx;
 }
+
+function makeMutClosure(arg0, arg1, dtor, f) {
+    const state = { a: arg0, b: arg1, cnt: 1, dtor };
+    const real = (...args) => {
+        // First up with a closure we increment the internal reference
+        // count. This ensures that the Rust closure environment won't
+        // be deallocated while we're invoking it.
+        state.cnt++;
+        const a = state.a;
+        state.a = 0;
+        try {
+            return f(a, state.b, ...args);
+        } finally {
+            if (--state.cnt === 0) {
+                wasm.__wbindgen_export_1.get(state.dtor)(a, state.b);
+
+            } else {
+                state.a = a;
+            }
+        }
+    };
+    real.original = state;
+
+    return real;
+}
+function __wbg_adapter_14(arg0, arg1, arg2) {
+    const ret = wasm._dyn_core__ops__function__FnMut__A____Output___R_as_wasm_bindgen__closure__WasmClosure___describe__invoke__hb5f328039aad7528(arg0, arg1, addHeapObject(arg2));
+    return takeObject(ret);
+}
+
+let cachegetInt32Memory0 = null;
+function getInt32Memory0() {
+    if (cachegetInt32Memory0 === null || cachegetInt32Memory0.buffer !== wasm.memory.buffer) {
+        cachegetInt32Memory0 = new Int32Array(wasm.memory.buffer);
+    }
+    return cachegetInt32Memory0;
+}
+
+function getArrayU8FromWasm0(ptr, len) {
+    return getUint8Memory0().subarray(ptr / 1, ptr / 1 + len);
+}
+
+let WASM_VECTOR_LEN = 0;
+
+function passArray8ToWasm0(arg, malloc) {
+    const ptr = malloc(arg.length * 1);
+    getUint8Memory0().set(arg, ptr / 1);
+    WASM_VECTOR_LEN = arg.length;
+    return ptr;
+}
+
+function isLikeNone(x) {
+    return x === undefined || x === null;
+}
+
+let cachegetUint16Memory0 = null;
+function getUint16Memory0() {
+    if (cachegetUint16Memory0 === null || cachegetUint16Memory0.buffer !== wasm.memory.buffer) {
+        cachegetUint16Memory0 = new Uint16Array(wasm.memory.buffer);
+    }
+    return cachegetUint16Memory0;
+}
+
+function passArray16ToWasm0(arg, malloc) {
+    const ptr = malloc(arg.length * 2);
+    getUint16Memory0().set(arg, ptr / 2);
+    WASM_VECTOR_LEN = arg.length;
+    return ptr;
+}
 /**
 * Function to be called in the web worker thread to compute the new layout.
 *
+* Returns once the channel with the `handle` is closed.
+*
 * # Safety
 *
 * Do not import this function as it is already imported into the web worker thread (see
 * `worker.js`).
+* @param {number} handle
 */
-export function run() {
-    wasm.run();
+export function run(handle) {
+    wasm.run(handle);
 }
 
 /**
 */
-export const MasonryType = Object.freeze({ Vertical:0,"0":"Vertical",Horizontal:1,"1":"Horizontal",Grid:2,"2":"Grid", });
+export const MasonryType = Object.freeze({ Vertical:0,"0":"Vertical",Horizontal:1,"1":"Horizontal",Grid:2,"2":"Grid",Justified:3,"3":"Justified",
+/**
+* Vertical masonry layout that places the items in reading order from left to right. A column
+* is only skipped if it is more than one column width taller than the shortest one.
+*/
+VerticalOrdered:4,"4":"VerticalOrdered",
+/**
+* Vertical masonry layout that places every row of as many items as there are columns
+* together, with the highest item in the shortest column.
+*/
+VerticalBalanced:5,"5":"VerticalBalanced",
+/**
+* Layout where the items keep the positions that were set for them and all other items are
+* placed at the lowest free spot at the thumbnail size, like a moodboard.
+*/
+Freeform:6,"6":"Freeform", });
+/**
+* Alignment of the last row of a horizontal or justified layout if it does not fill the container
+* width.
+*/
+export const LastRow = Object.freeze({
+/**
+* Keeps the items at the thumbnail size aligned to the left.
+*/
+Keep:0,"0":"Keep",
+/**
+* Scales the items so that they fill the container width.
+*/
+Justify:1,"1":"Justify",
+/**
+* Keeps the items at the thumbnail size in the center.
+*/
+Center:2,"2":"Center",
+/**
+* Moves items from the previous row into the last row, so that both rows have about the same
+* width.
+*/
+Balance:3,"3":"Balance", });
+/**
+* Direction in which the items are placed.
+*/
+export const Direction = Object.freeze({
+/**
+* Rows are filled from the left to the right and the container scrolls vertically.
+*/
+LeftToRight:0,"0":"LeftToRight",
+/**
+* Rows are filled from the right to the left and the container scrolls vertically.
+*/
+RightToLeft:1,"1":"RightToLeft",
+/**
+* Columns are filled from the top to the bottom and the container scrolls horizontally.
+*
+* Every layout is transposed: the container width becomes the height of the container and
+* the returned height becomes its width.
+*/
+TopToBottom:2,"2":"TopToBottom", });
+/**
+* Direction on the screen in which the next item is searched when navigating with the keyboard.
+*/
+export const NavigationDirection = Object.freeze({ Up:0,"0":"Up",Down:1,"1":"Down",Left:2,"2":"Left",Right:3,"3":"Right", });
+/**
+* How the number and width of the columns of a vertical or grid layout are chosen.
+*
+* The columns never exceed the container width. If they are narrower in total, the space after
+* the last column stays empty.
+*/
+export const ColumnMode = Object.freeze({
+/**
+* As many columns as fit at about the thumbnail size, which fill the container width.
+*/
+Auto:0,"0":"Auto",
+/**
+* A fixed number of columns that are as wide as the thumbnail size, or narrower if the
+* container is too narrow.
+*/
+Fixed:1,"1":"Fixed",
+/**
+* A fixed number of columns that fill the container width.
+*/
+Fit:2,"2":"Fit",
+/**
+* As many columns as fit at a minimum width, which are at most as wide as a maximum width.
+*/
+Width:3,"3":"Width", });
 /**
 */
 export class MasonryWorker {
@@ -83,21 +239,52 @@ export class MasonryWorker {
         wasm.__wbg_masonryworker_free(ptr);
     }
     /**
-    * Creates a new worker from a worker that was initialized with the `worker.js` script.
+    * Creates a new worker with its own channel to a web worker.
+    *
+    * The handle returned by [`MasonryWorker::get_handle()`] has to be sent to a web worker that
+    * was initialized with the `worker.js` script, which then computes the layouts of this
+    * instance. Throws an error if too many instances exist at the same time.
     * @param {number} num_items
     */
     constructor(num_items) {
-        const ret = wasm.masonryworker_new(num_items);
-        return MasonryWorker.__wrap(ret);
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_new(retptr, num_items);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return MasonryWorker.__wrap(r0);
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Returns the handle of the channel that has to be passed to `run` in the web worker.
+    * @returns {number}
+    */
+    get_handle() {
+        const ret = wasm.masonryworker_get_handle(this.ptr);
+        return ret >>> 0;
     }
     /**
     * Computes the transforms of all items.
     *
-    * # Safety
+    * If only dimensions were changed or items were added or removed since the previous
+    * computation with the same arguments, the layout is only computed again from the first
+    * affected row or column onwards.
     *
-    * The returned `Promise` must be `await`ed. Calls to any other method of [`MasonryWorker`]
-    * while the `Promise` is still pending will lead to undefined behaviour. As long as the value
-    * is `await`ed you can enjoy lock free concurrency.
+    * The returned `Promise` resolves to `"ok"` once the computation is finished. Calling this
+    * method again while the `Promise` is still pending cancels the pending computation at the
+    * next row or column and its `Promise` resolves to `"superseded"` right away. Only the most
+    * recent computation is finished, no matter how often this method was called in between.
+    *
+    * While a computation is running, the layout belongs to the worker thread. Changes to the
+    * items and groups are queued until the computation is finished or are applied by the next
+    * computation. Reading transforms throws an error until the `Promise` of the most recent
+    * computation is resolved, even while the worker thread switches between two computations.
     * @param {number} width
     * @param {number} kind
     * @param {number} thumbnail_size
@@ -109,7 +296,196 @@ export class MasonryWorker {
         return takeObject(ret);
     }
     /**
-    * Returns height of the container from the most recent computation.
+    * Serializes the most recent finished computation into a compact binary snapshot, for example
+    * to store it and show the layout immediately on the next start with
+    * [`MasonryWorker::restore()`].
+    *
+    * Returns `undefined` if items or settings were changed since the computation. Throws an
+    * error if a computation is running.
+    * @returns {Uint8Array | undefined}
+    */
+    snapshot() {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_snapshot(retptr, this.ptr);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            var r3 = getInt32Memory0()[retptr / 4 + 3];
+            if (r3) {
+                throw takeObject(r2);
+            }
+            let v0;
+            if (r0 !== 0) {
+                v0 = getArrayU8FromWasm0(r0, r1).slice();
+                wasm.__wbindgen_free(r0, r1 * 1);
+            }
+            return v0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Restores the items, groups and transforms of a snapshot from [`MasonryWorker::snapshot()`]
+    * instead of computing the layout, with the same arguments as [`MasonryWorker::compute()`].
+    *
+    * Returns the height of the container or `undefined` if the snapshot was taken with a
+    * different kind, width or other settings or is malformed, in which case nothing is changed
+    * and the layout has to be computed. [`MasonryWorker::get_height()`] is not updated by a
+    * restore. Setting the same dimensions afterwards does not change the layout, so the next
+    * computation with the same arguments finishes without computing anything. Throws an error if
+    * a computation is running.
+    * @param {Uint8Array} snapshot
+    * @param {number} width
+    * @param {number} kind
+    * @param {number} thumbnail_size
+    * @param {number} padding
+    * @returns {number | undefined}
+    */
+    restore(snapshot, width, kind, thumbnail_size, padding) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            const ptr0 = passArray8ToWasm0(snapshot, wasm.__wbindgen_malloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.masonryworker_restore(retptr, this.ptr, ptr0, len0, width, kind, thumbnail_size, padding);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            var r3 = getInt32Memory0()[retptr / 4 + 3];
+            if (r3) {
+                throw takeObject(r2);
+            }
+            return r0 === 0 ? undefined : r1 >>> 0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Sets how much the height of a row may deviate from the thumbnail size in percent for the
+    * [`MasonryType::Justified`] layout.
+    *
+    * The thumbnail size is the target height of all rows. A higher deviation results in fewer
+    * cropped looking rows but also in more uneven row heights. It also limits how much the
+    * previous row may be stretched with [`LastRow::Balance`]. The default is 25%.
+    * @param {number} percent
+    */
+    set_max_row_deviation(percent) {
+        wasm.masonryworker_set_max_row_deviation(this.ptr, percent);
+    }
+    /**
+    * Sets how the last row is aligned if it does not fill the container width for the
+    * [`MasonryType::Horizontal`] and [`MasonryType::Justified`] layouts.
+    *
+    * In grouped layouts this applies to the last row of every group. The default is
+    * [`LastRow::Keep`].
+    * @param {number} last_row
+    */
+    set_last_row(last_row) {
+        wasm.masonryworker_set_last_row(this.ptr, last_row);
+    }
+    /**
+    * Sets how many times as wide as high or vice versa an item can be at most.
+    *
+    * Items with more extreme aspect ratios are cropped, so that very narrow or wide images do
+    * not take up a whole row or column. Passing 0 disables cropping, e.g. for panoramas. The
+    * default is 3.
+    * @param {number} max_aspect_ratio
+    */
+    set_max_aspect_ratio(max_aspect_ratio) {
+        wasm.masonryworker_set_max_aspect_ratio(this.ptr, max_aspect_ratio);
+    }
+    /**
+    * Sets the direction in which the items are placed for all layouts.
+    *
+    * With [`Direction::TopToBottom`] the layout is transposed for horizontally scrolling
+    * containers like a filmstrip. The width passed to [`MasonryWorker::compute()`] is then the
+    * height of the container and [`MasonryWorker::get_height()`] returns its width. The default
+    * is [`Direction::LeftToRight`].
+    * @param {number} direction
+    */
+    set_direction(direction) {
+        wasm.masonryworker_set_direction(this.ptr, direction);
+    }
+    /**
+    * Sets the gap between the items next to each other for all layouts. Passing `undefined` uses
+    * the padding passed to [`MasonryWorker::compute()`], which is the default.
+    * @param {number | undefined} column_gap
+    */
+    set_column_gap(column_gap) {
+        wasm.masonryworker_set_column_gap(this.ptr, isLikeNone(column_gap) ? 0xFFFFFF : column_gap);
+    }
+    /**
+    * Sets the gap between the rows and groups for all layouts. Passing `undefined` uses the
+    * padding passed to [`MasonryWorker::compute()`], which is the default.
+    * @param {number | undefined} row_gap
+    */
+    set_row_gap(row_gap) {
+        wasm.masonryworker_set_row_gap(this.ptr, isLikeNone(row_gap) ? 0xFFFFFF : row_gap);
+    }
+    /**
+    * Sets the space between the container edges and the items for all layouts.
+    *
+    * The inset is part of the width passed to [`MasonryWorker::compute()`] and of the height
+    * returned by [`MasonryWorker::get_height()`]. The default is 0.
+    * @param {number} inset
+    */
+    set_inset(inset) {
+        wasm.masonryworker_set_inset(this.ptr, inset);
+    }
+    /**
+    * Sets the size of the grid to which the positions set with
+    * [`MasonryWorker::set_position()`] are rounded in the [`MasonryType::Freeform`] layout. The
+    * default is 0, which disables snapping.
+    * @param {number} snap
+    */
+    set_snap(snap) {
+        wasm.masonryworker_set_snap(this.ptr, snap);
+    }
+    /**
+    * Sets the height of the space below the image of every item for a caption, for example the
+    * file name, in all layouts.
+    *
+    * The space is added to the height of every [`Transform`] without distorting the aspect ratio
+    * of the image, which ends the caption height above the bottom of the transform. Items with a
+    * position set with [`MasonryWorker::set_position()`] keep their height. With
+    * [`Direction::TopToBottom`] the space is added to the width instead and the caption is right
+    * of the image. The default is 0.
+    * @param {number} caption_height
+    */
+    set_caption_height(caption_height) {
+        wasm.masonryworker_set_caption_height(this.ptr, caption_height);
+    }
+    /**
+    * Sets how the columns of the vertical masonry and [`MasonryType::Grid`] layouts are chosen.
+    *
+    * The meaning of the arguments depends on the mode:
+    * - [`ColumnMode::Auto`]: Both are ignored and the thumbnail size is the column width.
+    * - [`ColumnMode::Fixed`] and [`ColumnMode::Fit`]: `first` is the number of columns.
+    * - [`ColumnMode::Width`]: `first` is the minimum and `second` the maximum column width
+    *   without the gap. A maximum of 0 does not limit the width.
+    *
+    * The default is [`ColumnMode::Auto`].
+    * @param {number} mode
+    * @param {number} first
+    * @param {number} second
+    */
+    set_column_mode(mode, first, second) {
+        wasm.masonryworker_set_column_mode(this.ptr, mode, first, second);
+    }
+    /**
+    * Sets whether the transforms from before a computation are kept, so that changes of the
+    * layout can be animated with [`MasonryWorker::get_previous_transform()`] or
+    * [`MasonryWorker::interpolate()`]. The default is false.
+    * @param {boolean} retain_previous
+    */
+    set_retain_previous(retain_previous) {
+        wasm.masonryworker_set_retain_previous(this.ptr, retain_previous);
+    }
+    /**
+    * Returns height of the container from the most recent finished computation.
+    *
+    * The height ends at the bottom of the last item or header plus the inset, without a gap
+    * below them.
     * @returns {number}
     */
     get_height() {
@@ -117,6 +493,30 @@ export class MasonryWorker {
         return ret >>> 0;
     }
     /**
+    * Returns the scroll position that keeps the item passed to [`MasonryWorker::set_anchor()`] in
+    * place after the most recent finished computation.
+    *
+    * Returns `undefined` if no anchor was set before the computation or the item does not exist.
+    * Throws an error if a computation is running.
+    * @returns {number | undefined}
+    */
+    get_scroll_position() {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_get_scroll_position(retptr, this.ptr);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            var r3 = getInt32Memory0()[retptr / 4 + 3];
+            if (r3) {
+                throw takeObject(r2);
+            }
+            return r0 === 0 ? undefined : r1 >>> 0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
     * Set the number of items that need to be computed.
     *
     * Memory is never deallocated which means that even if the new len is smaller than the current
@@ -141,21 +541,363 @@ export class MasonryWorker {
         wasm.masonryworker_set_dimension(this.ptr, index, src_width, src_height);
     }
     /**
+    * Sets the number of columns and rows the item at the given index covers if it is smaller
+    * than the item count.
+    *
+    * Spans make it possible to highlight items in the [`MasonryType::Grid`] and vertical masonry
+    * layouts. Smaller items after them fill the holes they leave. In the vertical masonry
+    * layouts only the number of columns is used and groups with spans are always laid out like
+    * [`MasonryType::Vertical`]. The default is one column and one row.
+    * @param {number} index
+    * @param {number} columns
+    * @param {number} rows
+    */
+    set_span(index, columns, rows) {
+        wasm.masonryworker_set_span(this.ptr, index, columns, rows);
+    }
+    /**
+    * Keeps the item at the given index in place during the next computation, for example when
+    * the thumbnail size changes.
+    *
+    * The offset is the distance of the top of the item from the top of the viewport before the
+    * computation, which is negative if the item starts above the viewport. Afterwards
+    * [`MasonryWorker::get_scroll_position()`] returns the scroll position at which the item is at
+    * the same offset again. With [`Direction::TopToBottom`] the offset and the scroll position
+    * are horizontal.
+    * @param {number} index
+    * @param {number} offset
+    */
+    set_anchor(index, offset) {
+        wasm.masonryworker_set_anchor(this.ptr, index, offset);
+    }
+    /**
+    * Sets the width, height, top and left offset of the item at the given index in the
+    * [`MasonryType::Freeform`] layout, for example after it was dragged.
+    *
+    * The offsets are relative to the top of the group below its header and do not include the
+    * inset. With [`Direction::RightToLeft`] the left offset is mirrored and with
+    * [`Direction::TopToBottom`] the position is transposed like the rest of the layout. The item
+    * is moved into the container if it is outside and all items without a position are placed
+    * around it.
+    * @param {number} index
+    * @param {number} width
+    * @param {number} height
+    * @param {number} top
+    * @param {number} left
+    */
+    set_position(index, width, height, top, left) {
+        wasm.masonryworker_set_position(this.ptr, index, width, height, top, left);
+    }
+    /**
+    * Removes the position of the item at the given index, so that the
+    * [`MasonryType::Freeform`] layout places it automatically again.
+    * @param {number} index
+    */
+    clear_position(index) {
+        wasm.masonryworker_clear_position(this.ptr, index);
+    }
+    /**
+    * Sets the dimensions of the items from the first item onwards.
+    *
+    * The array contains the width and height of every item after each other. Setting all
+    * dimensions at once is a lot faster than calling [`MasonryWorker::set_dimension()`] for every
+    * item because it crosses the boundary between JavaScript and WebAssembly only once.
+    * @param {Uint16Array} dimensions
+    */
+    set_dimensions(dimensions) {
+        const ptr0 = passArray16ToWasm0(dimensions, wasm.__wbindgen_malloc);
+        const len0 = WASM_VECTOR_LEN;
+        wasm.masonryworker_set_dimensions(this.ptr, ptr0, len0);
+    }
+    /**
+    * Returns a pointer to a buffer with room for the width and height of every item.
+    *
+    * The width and height of every item are written after each other into the buffer. Afterwards
+    * [`MasonryWorker::flush_dimensions()`] has to be called to apply them. Unlike
+    * [`MasonryWorker::set_dimensions()`] the array does not need to be copied into the
+    * WebAssembly.Memory.
+    *
+    * # Safety
+    *
+    * The pointer is only valid until the next call to any method of [`MasonryWorker`] because
+    * the WebAssembly.Memory may grow and the buffer may be moved. Always request a new pointer
+    * before writing to the buffer.
+    * @returns {number}
+    */
+    dimensions_ptr() {
+        const ret = wasm.masonryworker_dimensions_ptr(this.ptr);
+        return ret;
+    }
+    /**
+    * Applies the dimensions written into the buffer returned by
+    * [`MasonryWorker::dimensions_ptr()`].
+    */
+    flush_dimensions() {
+        wasm.masonryworker_flush_dimensions(this.ptr);
+    }
+    /**
+    * Starts a new group with a header at the given item index.
+    *
+    * A group contains all items up to the start of the next group and is laid out independently
+    * from the other groups. The header is placed above the items of the group and spans the
+    * whole container width. Groups must be added in ascending order of their start index. Items
+    * in front of the first group are laid out without a header.
+    * @param {number} start
+    * @param {number} header_height
+    */
+    add_group(start, header_height) {
+        wasm.masonryworker_add_group(this.ptr, start, header_height);
+    }
+    /**
+    * Removes all groups, so that all items are laid out as one group without a header.
+    */
+    clear_groups() {
+        wasm.masonryworker_clear_groups(this.ptr);
+    }
+    /**
+    * Returns a pointer to the transform of the header of the group at the given index.
+    *
+    * Throws an error if a computation is running.
+    *
+    * # Safety
+    *
+    * If the index is greater than or equal to the number of groups, it will return a null
+    * pointer. Reading the WebAssembly.Memory will only return garbage. The pointer is only valid
+    * until the next call to [`MasonryWorker::compute()`].
+    * @param {number} index
+    * @returns {number}
+    */
+    get_header_transform(index) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_get_header_transform(retptr, this.ptr, index);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return r0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
     * Returns a pointer to the transform of the item at the given index.
     *
-    * The [`Transform`] object can be used to set the absolute position of an element.
+    * The [`Transform`] object can be used to set the absolute position of an element. Throws an
+    * error if a computation is running.
     *
     * # Safety
     *
     * If the index is greater than any number passed to [`MasonryWorker::resize()`], it will
-    * return a null pointer. Reading the WebAssembly.Memory will only return garbage.
+    * return a null pointer. Reading the WebAssembly.Memory will only return garbage. The pointer
+    * is only valid until the next call to [`MasonryWorker::compute()`].
     * @param {number} index
     * @returns {number}
     */
     get_transform(index) {
-        const ret = wasm.masonryworker_get_transform(this.ptr, index);
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_get_transform(retptr, this.ptr, index);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return r0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Returns a pointer to the transform of the item at the given index before the most recent
+    * computation.
+    *
+    * Throws an error if a computation is running.
+    *
+    * # Safety
+    *
+    * If the item did not exist before the most recent computation or previous transforms are not
+    * retained (see [`MasonryWorker::set_retain_previous()`]), it will return a null pointer. The
+    * pointer is only valid until the next call to [`MasonryWorker::compute()`].
+    * @param {number} index
+    * @returns {number}
+    */
+    get_previous_transform(index) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_get_previous_transform(retptr, this.ptr, index);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return r0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Returns a pointer to the transforms of the items from start to end (exclusive) interpolated
+    * between their previous and current transforms.
+    *
+    * `t` is the progress of the animation from 0 (previous) to 1 (current). Items that did not
+    * exist before the most recent computation stay at their current transform. Throws an error
+    * if a computation is running.
+    *
+    * # Safety
+    *
+    * The pointer can be read as an array of [`Transform`] objects, which is shorter than the
+    * range if it exceeds the number of items. It always points to the same buffer, which is
+    * overwritten by the next call to this method.
+    * @param {number} start
+    * @param {number} end
+    * @param {number} t
+    * @returns {number}
+    */
+    interpolate(start, end, t) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_interpolate(retptr, this.ptr, start, end, t);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return r0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Returns a pointer to the start and end (exclusive) index of the items which are visible
+    * between top and bottom in the most recent computation.
+    *
+    * The range is the smallest range that contains every visible item and works for every
+    * [`MasonryType`]. In the vertical masonry layout it can contain items that are not visible
+    * because the items of the columns are not sorted by their top offset. If no item is visible
+    * the range is empty.
+    *
+    * The pointer can be read as an array of two u32 and always points to the same location. Its
+    * values are overwritten by the next call to this method. Throws an error if a computation is
+    * running.
+    * @param {number} top
+    * @param {number} bottom
+    * @returns {number}
+    */
+    items_in_range(top, bottom) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_items_in_range(retptr, this.ptr, top, bottom);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return r0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Returns the index of the item at x and y in the most recent computation, for example to find
+    * the item under the pointer, or `undefined` if there is none.
+    *
+    * Throws an error if a computation is running.
+    * @param {number} x
+    * @param {number} y
+    * @returns {number | undefined}
+    */
+    item_at(x, y) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_item_at(retptr, this.ptr, x, y);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            var r3 = getInt32Memory0()[retptr / 4 + 3];
+            if (r3) {
+                throw takeObject(r2);
+            }
+            return r0 === 0 ? undefined : r1 >>> 0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Writes the indices of all items which intersect with the rectangle at x and y with the
+    * given width and height in the most recent computation into a buffer and returns their
+    * number.
+    *
+    * The indices are in ascending order and can be read with [`MasonryWorker::selection_ptr()`].
+    * Unlike [`MasonryWorker::items_in_range()`] only the items inside the rectangle are returned,
+    * for example for a rubber band selection. Throws an error if a computation is running.
+    * @param {number} x
+    * @param {number} y
+    * @param {number} width
+    * @param {number} height
+    * @returns {number}
+    */
+    items_in_rect(x, y, width, height) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_items_in_rect(retptr, this.ptr, x, y, width, height);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            if (r2) {
+                throw takeObject(r1);
+            }
+            return r0 >>> 0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
+    /**
+    * Returns a pointer to the indices written by [`MasonryWorker::items_in_rect()`].
+    *
+    * # Safety
+    *
+    * The pointer can be read as an array of as many u32 as returned by
+    * [`MasonryWorker::items_in_rect()`]. It is only valid until the next call to that method.
+    * @returns {number}
+    */
+    selection_ptr() {
+        const ret = wasm.masonryworker_selection_ptr(this.ptr);
         return ret;
     }
+    /**
+    * Returns the index of the item next to the item at the given index in the direction on the
+    * screen, for example to move the focus with the arrow keys.
+    *
+    * The neighbor is found by the transforms of the most recent computation, so it works for
+    * every [`MasonryType`] and [`Direction`]. Returns `undefined` if there is no item in the
+    * direction or the index is out of bounds. Throws an error if a computation is running.
+    * @param {number} index
+    * @param {number} direction
+    * @returns {number | undefined}
+    */
+    neighbor(index, direction) {
+        try {
+            const retptr = wasm.__wbindgen_add_to_stack_pointer(-16);
+            wasm.masonryworker_neighbor(retptr, this.ptr, index, direction);
+            var r0 = getInt32Memory0()[retptr / 4 + 0];
+            var r1 = getInt32Memory0()[retptr / 4 + 1];
+            var r2 = getInt32Memory0()[retptr / 4 + 2];
+            var r3 = getInt32Memory0()[retptr / 4 + 3];
+            if (r3) {
+                throw takeObject(r2);
+            }
+            return r0 === 0 ? undefined : r1 >>> 0;
+        } finally {
+            wasm.__wbindgen_add_to_stack_pointer(16);
+        }
+    }
 }
 
 async function load(module, imports) {
@@ -193,13 +935,26 @@ async function init(input, maybe_memory) {
 
     const imports = {};
     imports.wbg = {};
+    imports.wbg.__wbindgen_object_drop_ref = function(arg0) {
+        takeObject(arg0);
+    };
+    imports.wbg.__wbindgen_cb_drop = function(arg0) {
+        const obj = takeObject(arg0).original;
+        if (obj.cnt-- == 1) {
+            obj.a = 0;
+            return true;
+        }
+        const ret = false;
+        return ret;
+    };
+    imports.wbg.__wbindgen_string_new = function(arg0, arg1) {
+        const ret = getStringFromWasm0(arg0, arg1);
+        return addHeapObject(ret);
+    };
     imports.wbg.__wbg_waitAsync_df6dd3a2a5307a2a = function(arg0, arg1, arg2) {
         const ret = Atomics.waitAsync(getObject(arg0), arg1, arg2);
         return addHeapObject(ret);
     };
-    imports.wbg.__wbindgen_object_drop_ref = function(arg0) {
-        takeObject(arg0);
-    };
     imports.wbg.__wbg_async_b131bfa206aa5cd9 = function(arg0) {
         const ret = getObject(arg0).async;
         return ret;
@@ -208,6 +963,14 @@ async function init(input, maybe_memory) {
         const ret = getObject(arg0).value;
         return addHeapObject(ret);
     };
+    imports.wbg.__wbindgen_object_clone_ref = function(arg0) {
+        const ret = getObject(arg0);
+        return addHeapObject(ret);
+    };
+    imports.wbg.__wbg_then_ba8871267286c6d9 = function(arg0, arg1) {
+        const ret = getObject(arg0).then(getObject(arg1));
+        return addHeapObject(ret);
+    };
     imports.wbg.__wbg_buffer_7af23f65f6c64548 = function(arg0) {
         const ret = getObject(arg0).buffer;
         return addHeapObject(ret);
@@ -227,6 +990,10 @@ async function init(input, maybe_memory) {
         const ret = wasm.memory;
         return addHeapObject(ret);
     };
+    imports.wbg.__wbindgen_closure_wrapper80 = function(arg0, arg1, arg2) {
+        const ret = makeMutClosure(arg0, arg1, 10, __wbg_adapter_14);
+        return addHeapObject(ret);
+    };
 
     if (typeof input === 'string' || (typeof Request === 'function' && input instanceof Request) || (typeof URL === 'function' && input instanceof URL)) {
         input = fetch(input);
@@ -1,5 +1,6 @@
 import { default as init, run } from './index.js';
 self.onmessage = async (event) => {
-  await init(event.data);
-  run();
+  const { memory, handle } = event.data;
+  await init(memory);
+  run(handle);
 };